@@ -0,0 +1,175 @@
+// compares the row based drawing of `Pixels` with the per-pixel drawing that it replaced.
+// run with:
+//     cargo bench --bench pixels
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// the bar is a binary, so the module is included from its source
+#[allow(dead_code, unused_imports)]
+#[path = "../src"]
+mod src {
+    pub mod pixels;
+}
+
+use src::pixels::{Color, Pixels, Rect};
+
+// a bar on a 2560 pixels wide monitor
+const WIDTH: u32 = 2560;
+const HEIGHT: u32 = 48;
+
+// how long each case runs, after warming up for a tenth of it
+const DURATION: Duration = Duration::from_millis(500);
+
+// the drawing as it was done before, on plain memory
+mod per_pixel {
+    use super::Color;
+
+    pub struct Pixels {
+        pub bytes: Vec<u8>,
+        pub width: u32,
+        pub height: u32,
+    }
+
+    impl Pixels {
+        pub fn new(width: u32, height: u32) -> Self {
+            let bytes = vec![0; (width * height * 4) as usize];
+            Self {
+                bytes,
+                width,
+                height,
+            }
+        }
+
+        pub fn clear(&mut self, color: Color) {
+            let mut bytes = color.as_argb().into_iter().cycle();
+            self.bytes.fill_with(|| bytes.next().unwrap_or_default());
+        }
+
+        pub fn set(&mut self, x: u32, y: u32, color: Color) -> bool {
+            if x >= self.width || y >= self.height {
+                return false;
+            }
+
+            let index = (x * 4 + y * self.width * 4) as usize;
+            self.bytes[index..index + 4].copy_from_slice(&color.as_argb());
+
+            true
+        }
+    }
+
+    pub fn interpolate(this: Color, other: Color, f: f32) -> Color {
+        let f = f.clamp(0., 1.);
+
+        let r = this.r as f32 * (1. - f) + other.r as f32 * f;
+        let g = this.g as f32 * (1. - f) + other.g as f32 * f;
+        let b = this.b as f32 * (1. - f) + other.b as f32 * f;
+        let a = this.a as f32 * (1. - f) + other.a as f32 * f;
+
+        let r = r.round().clamp(0., 255.) as u8;
+        let g = g.round().clamp(0., 255.) as u8;
+        let b = b.round().clamp(0., 255.) as u8;
+        let a = a.round().clamp(0., 255.) as u8;
+
+        Color::new(r, g, b, a)
+    }
+}
+
+// the average time of a single call
+fn measure(mut f: impl FnMut()) -> Duration {
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < DURATION / 10 {
+        f();
+    }
+
+    let start = Instant::now();
+    let mut iterations = 0;
+    while start.elapsed() < DURATION {
+        f();
+        iterations += 1;
+    }
+
+    start.elapsed() / iterations
+}
+
+fn compare(name: &str, before: impl FnMut(), after: impl FnMut()) {
+    let before = measure(before);
+    let after = measure(after);
+
+    println!(
+        "{name:<12} per pixel: {:>10.2?}   rows: {:>10.2?}   {:>6.1}x faster",
+        before,
+        after,
+        before.as_secs_f64() / after.as_secs_f64()
+    );
+}
+
+fn main() {
+    let bg = Color::new(0x20, 0x20, 0x20, 0xff);
+    let fg = Color::new(0xee, 0xcc, 0xaa, 0xff);
+
+    let mut old = per_pixel::Pixels::new(WIDTH, HEIGHT);
+    let mut new = Pixels::new(WIDTH, HEIGHT);
+
+    compare(
+        "clear",
+        || old.clear(black_box(bg)),
+        || new.clear(black_box(bg)),
+    );
+
+    // the background of a run that is a third of the bar
+    let (start, end) = (WIDTH / 3, WIDTH * 2 / 3);
+    compare(
+        "fill_rect",
+        || {
+            for x in start..end {
+                for y in 0..HEIGHT {
+                    old.set(x, y, black_box(bg));
+                }
+            }
+        },
+        || {
+            let rect = Rect {
+                x: start as i64..end as i64,
+                y: 0..HEIGHT as i64,
+            };
+            new.fill_rect(&rect, black_box(bg));
+        },
+    );
+
+    // a line of text, with glyphs that are mostly empty or fully covered like real ones
+    let (glyph_width, glyph_height) = (18, 30);
+    let mask: Vec<u8> = (0..glyph_width * glyph_height)
+        .map(|i| match i % 7 {
+            0..=2 => 0,
+            3 | 4 => 255,
+            n => n as u8 * 40,
+        })
+        .collect();
+    let glyphs = 100;
+
+    compare(
+        "blend_mask",
+        || {
+            for glyph in 0..glyphs {
+                for y in 0..glyph_height {
+                    for x in 0..glyph_width {
+                        let f = mask[(y * glyph_width + x) as usize] as f32 / 255.;
+                        let color = per_pixel::interpolate(bg, fg, f);
+                        old.set(glyph * 20 + x, 9 + y, black_box(color));
+                    }
+                }
+            }
+        },
+        || {
+            let clip = Rect {
+                x: 0..WIDTH as i64,
+                y: 0..HEIGHT as i64,
+            };
+
+            for glyph in 0..glyphs {
+                let x = glyph as i64 * 20;
+                new.blend_mask(x, 9, &mask, glyph_width, &clip, black_box(fg));
+            }
+        },
+    );
+}
@@ -1,80 +1,93 @@
-use ab_glyph::{Font as _, FontVec, PxScale, PxScaleFont};
-use rust_fontconfig::{FcFontCache, FcPattern};
+use std::collections::HashMap;
+use std::io::{self, Write as _};
+
+use wayland_client::backend::ObjectId;
 use wayland_client::protocol::{
-    wl_buffer, wl_compositor, wl_output, wl_registry, wl_shm, wl_shm_pool, wl_surface,
+    wl_buffer, wl_callback, wl_compositor, wl_output, wl_pointer, wl_registry, wl_seat, wl_shm,
+    wl_shm_pool, wl_surface,
+};
+use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, WEnum, delegate_noop};
+use wayland_protocols::wp::fractional_scale::v1::client::{
+    wp_fractional_scale_manager_v1, wp_fractional_scale_v1,
 };
-use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, delegate_noop};
+use wayland_protocols::wp::viewporter::client::{wp_viewport, wp_viewporter};
 use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
 
 use crate::bench;
+use crate::collector::Globals;
 use crate::config::Config;
+use crate::control::Command;
+use crate::filter::OutputFilter;
+use crate::font::Fonts;
+use crate::glyph_cache::GlyphCache;
 use crate::output::Output;
-use crate::parser::Section;
-use crate::pixels::Pixels;
+use crate::render::layout;
 use crate::token::Token;
 
-pub struct SectionInfo<'a> {
-    pub width: f32,
-    pub mult: f32,
-    pub indices: &'a [usize],
+// a wl_output advertised by the compositor, which may or may not have a bar on it
+struct Head {
+    global: u32,
+    output: wl_output::WlOutput,
+    name: String,
+    description: String,
+
+    // the integer scale, which is only used when there's no fractional one
+    scale: u32,
+}
+
+// the surface that the pointer is currently on, and the pointer's position on it
+struct PointerFocus {
+    surface: ObjectId,
+    x: f64,
+    y: f64,
 }
 
 pub struct Bar {
     running: bool,
     config: Config,
-    shm: wl_shm::WlShm,
-    compositor: wl_compositor::WlCompositor,
-    layer_shell: zwlr_layer_shell_v1::ZwlrLayerShellV1,
-    font: PxScaleFont<FontVec>,
-    outputs: Vec<Output>,
-    prev_widths: [f32; 3],
-}
+    filter: OutputFilter,
+    heads: Vec<Head>,
+    globals: Globals,
 
-impl Bar {
-    pub fn new(
-        compositor: wl_compositor::WlCompositor,
-        shm: wl_shm::WlShm,
-        layer_shell: zwlr_layer_shell_v1::ZwlrLayerShellV1,
-        mut config: Config,
-    ) -> Self {
-        let fc = FcFontCache::build();
-
-        let pattern = if let Some(font) = config.font.take() {
-            FcPattern {
-                name: Some(font),
-                ..Default::default()
-            }
-        } else {
-            FcPattern::default()
-        };
+    // the fonts at each of the sizes that the outputs need, which depends on their scale
+    fonts: HashMap<u32, Fonts>,
 
-        let Some(m) = fc.query(&pattern, &mut Vec::new()) else {
-            if let Some(name) = pattern.name {
-                eprintln!("ERROR: no such font '{}'", name);
-            } else {
-                eprintln!("ERROR: no font available");
-            }
+    // shared by all of the outputs, since most of them show the same characters at the same size
+    glyphs: GlyphCache,
+    outputs: Vec<Output>,
+    pointer: Option<wl_pointer::WlPointer>,
+    pointer_focus: Option<PointerFocus>,
 
-            std::process::exit(1);
-        };
+    // set when an output may be able to draw the last line, which it didn't draw yet
+    needs_redraw: bool,
 
-        let font_data = fc.get_font_bytes(&m.id).expect("font should be accessible");
+    // applies to every output, including the ones that are plugged in while hidden
+    hidden: bool,
+}
 
-        let scale = PxScale::from(config.font_size as f32);
-        let font = FontVec::try_from_vec(font_data).unwrap();
-        let font = font.into_scaled(scale);
+impl Bar {
+    pub fn new(globals: Globals, config: Config) -> Self {
+        let fonts = HashMap::from([(
+            config.font_size,
+            Fonts::load(&config.font, config.font_size),
+        )]);
 
         let outputs = Vec::new();
+        let filter = OutputFilter::new(&config);
 
         Self {
-            running: false,
-            shm,
-            font,
-            compositor,
-            layer_shell,
+            running: true,
+            filter,
+            heads: Vec::new(),
+            globals,
+            fonts,
+            glyphs: GlyphCache::default(),
             outputs,
             config,
-            prev_widths: Default::default(),
+            pointer: None,
+            pointer_focus: None,
+            needs_redraw: false,
+            hidden: false,
         }
     }
 
@@ -82,83 +95,213 @@ impl Bar {
         self.running
     }
 
+    pub fn is_configured(&self) -> bool {
+        self.outputs.iter().all(|o| o.configured)
+    }
+
     pub fn stop_running(&mut self) {
         self.running = false;
     }
 
-    pub fn draw_tokens(&mut self, tokens: &[Token]) {
-        let mut l = Vec::new();
-        let mut c = Vec::new();
-        let mut r = Vec::new();
-        let mut ptr = &mut l;
-
-        // collect all token indices to their correct section
-        for (index, token) in tokens.iter().enumerate() {
-            match token {
-                Token::Section(Section::Left) => ptr = &mut l,
-                Token::Section(Section::Center) => ptr = &mut c,
-                Token::Section(Section::Right) => ptr = &mut r,
-                _ => ptr.push(index),
-            }
+    pub fn take_needs_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.needs_redraw)
+    }
+
+    // applies a new config by re-creating all of the outputs
+    pub fn reload(&mut self, mut config: Config, qhandle: &QueueHandle<Self>) {
+        // the input and the control socket are set up once, so these keep their running values
+        let changed: Vec<_> = [
+            ("strict", config.strict != self.config.strict),
+            ("on-eof", config.on_eof != self.config.on_eof),
+            ("blocks", config.blocks != self.config.blocks),
+            ("command", config.command != self.config.command),
+            ("name", config.name != self.config.name),
+        ]
+        .into_iter()
+        .filter_map(|(option, changed)| changed.then_some(option))
+        .collect();
+
+        if !changed.is_empty() {
+            eprintln!(
+                "WARN: {} only take effect after a restart",
+                changed.join(", ")
+            );
         }
 
-        // width of left is used for damage
-        let l_width: f32 = l
-            .iter()
-            .map(|&index| tokens[index].px_width(&self.font))
-            .sum();
+        config.strict = self.config.strict;
+        config.on_eof = self.config.on_eof;
+        config.blocks = self.config.blocks.take();
+        config.command = std::mem::take(&mut self.config.command);
+        config.name = std::mem::take(&mut self.config.name);
+
+        if config.font != self.config.font || config.font_size != self.config.font_size {
+            self.fonts.clear();
+            self.glyphs.clear();
+            self.fonts.insert(
+                config.font_size,
+                Fonts::load(&config.font, config.font_size),
+            );
+        }
 
-        let c_width: f32 = c
-            .iter()
-            .map(|&index| tokens[index].px_width(&self.font))
-            .sum();
+        self.filter = OutputFilter::new(&config);
+        self.config = config;
+        self.recreate_outputs(qhandle);
+    }
 
-        let r_width: f32 = r
-            .iter()
-            .map(|&index| tokens[index].px_width(&self.font))
-            .sum::<f32>();
-
-        // since each output has it's own width, the calculation of the starting pixel had to be
-        // abstracted away.
-        let l_section = SectionInfo {
-            width: l_width,
-            mult: 0., // start = (pixels - width) * 0
-            indices: l.as_slice(),
-        };
+    pub fn run_command(&mut self, command: Command, qhandle: &QueueHandle<Self>) {
+        match command {
+            Command::Hide => self.set_hidden(true, qhandle),
+            Command::Show => self.set_hidden(false, qhandle),
+            Command::Toggle => self.set_hidden(!self.hidden, qhandle),
+            Command::Height(height) => self.update_config(|config| config.height = height),
+            Command::Fg(color) => self.update_config(|config| config.fg = color),
+            Command::Bg(color) => self.update_config(|config| config.bg = color),
+            Command::Position(position) => self.update_config(|config| config.position = position),
+            Command::Layer(layer) => self.update_config(|config| config.layer = layer),
+        }
+    }
 
-        let c_section = SectionInfo {
-            width: c_width,
-            mult: 0.5, // start = (pixels - width) * 0.5
-            indices: c.as_slice(),
-        };
+    pub fn set_hidden(&mut self, hidden: bool, qhandle: &QueueHandle<Self>) {
+        if hidden == self.hidden {
+            return;
+        }
 
-        let r_section = SectionInfo {
-            width: r_width,
-            mult: 1., // start = (pixels - width) * 1
-            indices: r.as_slice(),
-        };
+        self.hidden = hidden;
+        if hidden {
+            self.outputs.iter_mut().for_each(Output::hide);
+        } else {
+            // an unmapped layer surface has to be configured from scratch before it's mapped again
+            self.recreate_outputs(qhandle);
+        }
+    }
 
-        let sections = [l_section, c_section, r_section];
+    // changes the config, and applies it to the existing outputs
+    pub fn update_config(&mut self, update: impl FnOnce(&mut Config)) {
+        update(&mut self.config);
 
-        bench!("render", {
-            for output in &mut self.outputs {
-                output.draw(tokens, &sections, &self.font);
-            }
-        });
+        // the hidden outputs get the new config when they're created again
+        if self.hidden {
+            return;
+        }
+
+        for output in &mut self.outputs {
+            output.dirty = true;
+
+            let Some(head) = self
+                .heads
+                .iter()
+                .find(|h| h.output.id() == output.output.id())
+            else {
+                continue;
+            };
+
+            output.apply(&self.config.for_output(&head.name, &head.description));
+        }
 
-        bench!("refresh", self.refresh(sections));
+        // changes like the colors don't get a new buffer, but still have to be drawn
+        self.needs_redraw = true;
     }
 
-    fn refresh(&mut self, mut sections: [SectionInfo; 3]) {
-        let new_widths = sections.each_ref().map(|s| s.width);
-        let widths: [_; 3] = std::array::from_fn(|i| new_widths[i].max(self.prev_widths[i]));
+    fn recreate_outputs(&mut self, qhandle: &QueueHandle<Self>) {
+        self.outputs.clear();
+        for index in 0..self.heads.len() {
+            self.create_output(index, qhandle);
+        }
+    }
 
-        for (section, width) in sections.iter_mut().zip(widths) {
-            section.width = width;
+    // creates a bar on the head, if the rules allow it
+    fn create_output(&mut self, index: usize, qhandle: &QueueHandle<Self>) {
+        // created when the bar is shown again
+        if self.hidden {
+            return;
         }
 
-        self.outputs.iter_mut().for_each(|o| o.refresh(&sections));
-        self.prev_widths = new_widths;
+        let head = &self.heads[index];
+
+        // the rules are re-evaluated on every change, so hot-plugged outputs are handled the same
+        // as the ones that existed on startup
+        if !self.filter.matches(&head.name, &head.description) {
+            return;
+        }
+
+        let config = self.config.for_output(&head.name, &head.description);
+        let output = Output::create(
+            qhandle,
+            &self.globals,
+            head.output.clone(),
+            head.name.clone(),
+            &config,
+            head.scale,
+        );
+
+        self.outputs.push(output);
+    }
+
+    // loads the fonts at the sizes that the outputs need, from the fonts that are already loaded
+    fn load_font_sizes(&mut self) {
+        for output in &self.outputs {
+            let size = output.style.scaled_font_size();
+            if self.fonts.contains_key(&size) {
+                continue;
+            }
+
+            let fonts = match self.fonts.values().next() {
+                Some(fonts) => fonts.resized(size),
+                None => Fonts::load(&self.config.font, size),
+            };
+
+            self.fonts.insert(size, fonts);
+        }
+    }
+
+    // draws a new line on every output that is ready for it, and the rest draw it once they are
+    pub fn draw_tokens(&mut self, tokens: &[Token], qhandle: &QueueHandle<Self>) {
+        for output in &mut self.outputs {
+            output.dirty = true;
+        }
+
+        self.draw_dirty(tokens, qhandle);
+    }
+
+    // draws the line on the outputs that didn't draw it yet, and are ready for it
+    pub fn draw_dirty(&mut self, tokens: &[Token], qhandle: &QueueHandle<Self>) {
+        self.load_font_sizes();
+
+        // each output may be showing different tokens, so the sections are laid out per output
+        let sections: Vec<_> = self
+            .outputs
+            .iter()
+            .map(|output| {
+                let fonts = &self.fonts[&output.style.scaled_font_size()];
+                output
+                    .is_ready()
+                    .then(|| layout(tokens, &output.name, fonts, &output.style))
+            })
+            .collect();
+
+        bench!("render", {
+            for (output, sections) in self.outputs.iter_mut().zip(&sections) {
+                let Some(sections) = sections else {
+                    continue;
+                };
+
+                let fonts = &self.fonts[&output.style.scaled_font_size()];
+                output.draw(
+                    tokens,
+                    sections,
+                    fonts,
+                    &mut self.glyphs,
+                    &self.globals.shm,
+                    qhandle,
+                );
+            }
+        });
+
+        bench!("refresh", {
+            for output in &mut self.outputs {
+                output.refresh(qhandle);
+            }
+        });
     }
 }
 
@@ -166,26 +309,53 @@ delegate_noop!(Bar: ignore wl_compositor::WlCompositor);
 delegate_noop!(Bar: ignore wl_surface::WlSurface);
 delegate_noop!(Bar: ignore wl_shm::WlShm);
 delegate_noop!(Bar: ignore wl_shm_pool::WlShmPool);
-delegate_noop!(Bar: ignore wl_buffer::WlBuffer);
 delegate_noop!(Bar: ignore zwlr_layer_shell_v1::ZwlrLayerShellV1);
+delegate_noop!(Bar: ignore wp_viewporter::WpViewporter);
+delegate_noop!(Bar: ignore wp_viewport::WpViewport);
+delegate_noop!(Bar: ignore wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1);
 
 impl Dispatch<wl_registry::WlRegistry, ()> for Bar {
     fn event(
-        _: &mut Self,
+        state: &mut Self,
         proxy: &wl_registry::WlRegistry,
         event: <wl_registry::WlRegistry as Proxy>::Event,
         _: &(),
         _: &Connection,
         qhandle: &QueueHandle<Self>,
     ) {
-        if let wl_registry::Event::Global {
-            name,
-            interface,
-            version,
-        } = event
-            && interface == "wl_output"
-        {
-            proxy.bind::<wl_output::WlOutput, _, _>(name, version, qhandle, ());
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } if interface == "wl_output" => {
+                // version 4 is needed for the name and description events
+                let output =
+                    proxy.bind::<wl_output::WlOutput, _, _>(name, version.min(4), qhandle, ());
+
+                state.heads.push(Head {
+                    global: name,
+                    output,
+                    name: String::new(),
+                    description: String::new(),
+                    scale: 1,
+                });
+            }
+
+            wl_registry::Event::GlobalRemove { name } => {
+                let Some(index) = state.heads.iter().position(|h| h.global == name) else {
+                    return;
+                };
+
+                let head = state.heads.swap_remove(index);
+                state.outputs.retain(|o| o.output.id() != head.output.id());
+
+                if head.output.version() >= 3 {
+                    head.output.release();
+                }
+            }
+
+            _ => {}
         }
     }
 }
@@ -199,28 +369,170 @@ impl Dispatch<wl_output::WlOutput, ()> for Bar {
         _: &Connection,
         qhandle: &QueueHandle<Self>,
     ) {
-        if let wl_output::Event::Done = event {
-            if let Some(index) = state
-                .outputs
-                .iter()
-                .position(|o| o.output.id() == proxy.id())
-            {
-                state.outputs.swap_remove(index);
+        let Some(head_index) = state.heads.iter().position(|h| h.output.id() == proxy.id()) else {
+            return;
+        };
+
+        let head = &mut state.heads[head_index];
+        match event {
+            wl_output::Event::Name { name } => head.name = name,
+            wl_output::Event::Description { description } => head.description = description,
+            wl_output::Event::Scale { factor } => head.scale = factor.max(1) as u32,
+            wl_output::Event::Done => {
+                if let Some(index) = state
+                    .outputs
+                    .iter()
+                    .position(|o| o.output.id() == proxy.id())
+                {
+                    state.outputs.swap_remove(index);
+                }
+
+                state.create_output(head_index, qhandle);
             }
 
-            let output = Output::create(
-                qhandle,
-                &state.compositor,
-                &state.layer_shell,
-                &state.shm,
-                proxy.clone(),
-                &state.config,
-            );
-            state.outputs.push(output);
+            _ => {}
         }
     }
 }
 
+impl Dispatch<wl_seat::WlSeat, ()> for Bar {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_seat::WlSeat,
+        event: <wl_seat::WlSeat as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        qhandle: &QueueHandle<Self>,
+    ) {
+        // the bar stays non-interactive unless asked otherwise
+        if !state.config.clickable {
+            return;
+        }
+
+        let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(capabilities),
+        } = event
+        else {
+            return;
+        };
+
+        let has_pointer = capabilities.contains(wl_seat::Capability::Pointer);
+        match state.pointer.take() {
+            Some(pointer) if !has_pointer => {
+                if pointer.version() >= 3 {
+                    pointer.release();
+                }
+            }
+            None if has_pointer => state.pointer = Some(proxy.get_pointer(qhandle, ())),
+            pointer => state.pointer = pointer,
+        }
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for Bar {
+    fn event(
+        state: &mut Self,
+        _: &wl_pointer::WlPointer,
+        event: <wl_pointer::WlPointer as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                state.pointer_focus = Some(PointerFocus {
+                    surface: surface.id(),
+                    x: surface_x,
+                    y: surface_y,
+                })
+            }
+
+            wl_pointer::Event::Leave { .. } => state.pointer_focus = None,
+
+            wl_pointer::Event::Motion {
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                if let Some(focus) = &mut state.pointer_focus {
+                    focus.x = surface_x;
+                    focus.y = surface_y;
+                }
+            }
+
+            wl_pointer::Event::Button {
+                button,
+                state: WEnum::Value(wl_pointer::ButtonState::Pressed),
+                ..
+            } => {
+                let Some(focus) = &state.pointer_focus else {
+                    return;
+                };
+
+                let Some(button) = x11_button(button) else {
+                    return;
+                };
+
+                let Some(output) = state
+                    .outputs
+                    .iter()
+                    .find(|o| o.wl_surface.id() == focus.surface)
+                else {
+                    return;
+                };
+
+                let Some(command) = output.action_at(focus.x as f32, focus.y as f32) else {
+                    return;
+                };
+
+                // the feeder decides what to do with the command, so it's flushed right away
+                let mut stdout = io::stdout().lock();
+                let res = writeln!(stdout, "{button} {command}").and_then(|()| stdout.flush());
+
+                // nobody reads the clicks anymore, so the pointer is let go
+                if let Err(err) = res {
+                    eprintln!(
+                        "ERROR: can't print the click, ignoring the clicks from now on: {}",
+                        err
+                    );
+                    state.config.clickable = false;
+                    state.pointer_focus = None;
+                    if let Some(pointer) = state.pointer.take()
+                        && pointer.version() >= 3
+                    {
+                        pointer.release();
+                    }
+                }
+            }
+
+            _ => {}
+        }
+    }
+}
+
+// converts linux input event codes to the button numbers that X11 uses
+fn x11_button(button: u32) -> Option<u32> {
+    const BTN_LEFT: u32 = 0x110;
+    const BTN_RIGHT: u32 = 0x111;
+    const BTN_MIDDLE: u32 = 0x112;
+    const BTN_SIDE: u32 = 0x113;
+    const BTN_EXTRA: u32 = 0x114;
+
+    match button {
+        BTN_LEFT => Some(1),
+        BTN_MIDDLE => Some(2),
+        BTN_RIGHT => Some(3),
+        BTN_SIDE => Some(8),
+        BTN_EXTRA => Some(9),
+        _ => None,
+    }
+}
+
 impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for Bar {
     fn event(
         state: &mut Self,
@@ -262,38 +574,93 @@ impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()> for Bar {
                 // tell the proxy that you acknowledge the config request
                 proxy.ack_configure(serial);
 
-                state.running = true;
+                // attaching a buffer would map the surface again
+                if output.hidden {
+                    return;
+                }
 
-                // create new shared memory
-                output.pixels = Pixels::new(width, height);
-                let size = output.pixels.size() as i32;
+                output.size = (width, height);
+                output.allocate(&state.globals.shm, qhandle);
+                output.configured = true;
+                state.needs_redraw = true;
+            }
 
-                // make sure everything is initialized to bg color instead of transparent
-                output.pixels.clear(output.bg);
+            _ => {}
+        }
+    }
+}
 
-                let pool = state
-                    .shm
-                    .create_pool(output.pixels.as_fd(), size, qhandle, ());
-                output.buffer.destroy();
+impl Dispatch<wl_buffer::WlBuffer, ()> for Bar {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_buffer::WlBuffer,
+        event: <wl_buffer::WlBuffer as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wl_buffer::Event::Release = event else {
+            return;
+        };
 
-                let stride = width * 4;
-                output.buffer = pool.create_buffer(
-                    0,
-                    width as i32,
-                    height as i32,
-                    stride as i32,
-                    wl_shm::Format::Argb8888,
-                    qhandle,
-                    (),
-                );
+        // the frame that had no buffer to be drawn into is drawn now
+        for output in &mut state.outputs {
+            if output.release(proxy) {
+                state.needs_redraw = true;
+            }
+        }
+    }
+}
 
-                // attach the new buffer to the surface
-                output.wl_surface.attach(Some(&output.buffer), 0, 0);
-                output.wl_surface.commit();
-                output.configured = true;
+impl Dispatch<wl_callback::WlCallback, ()> for Bar {
+    fn event(
+        state: &mut Self,
+        proxy: &wl_callback::WlCallback,
+        event: <wl_callback::WlCallback as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let wl_callback::Event::Done { .. } = event else {
+            return;
+        };
+
+        // the lines that came while waiting for the frame are drawn now, only the latest one
+        for output in &mut state.outputs {
+            if output.frame_done(proxy) {
+                state.needs_redraw = true;
             }
+        }
+    }
+}
 
-            _ => {}
+impl Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, ()> for Bar {
+    fn event(
+        state: &mut Self,
+        proxy: &wp_fractional_scale_v1::WpFractionalScaleV1,
+        event: <wp_fractional_scale_v1::WpFractionalScaleV1 as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        qhandle: &QueueHandle<Self>,
+    ) {
+        let wp_fractional_scale_v1::Event::PreferredScale { scale } = event else {
+            return;
+        };
+
+        let Some(output) = state.outputs.iter_mut().find(|o| {
+            o.fractional_scale
+                .as_ref()
+                .is_some_and(|f| f.id() == proxy.id())
+        }) else {
+            return;
+        };
+
+        // the first configure allocates the buffer with the new scale
+        if !output.set_scale(scale) || !output.configured || output.hidden {
+            return;
         }
+
+        output.allocate(&state.globals.shm, qhandle);
+        state.needs_redraw = true;
     }
 }
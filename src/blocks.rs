@@ -0,0 +1,395 @@
+use std::fs::{self, File};
+use std::mem::MaybeUninit;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+use nix::sys::signal::SigSet;
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::sys::time::TimeSpec;
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
+use serde::Deserialize;
+
+use crate::feeder::reap;
+use crate::line_reader::{Input, LineReader};
+use crate::parser::Section;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlocksConfig {
+    // put between the blocks of the same section
+    #[serde(default = "default_separator")]
+    pub separator: String,
+
+    #[serde(default, rename = "block")]
+    pub blocks: Vec<BlockConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockConfig {
+    pub name: String,
+
+    // runs with `sh -c`, and the last line it printed is shown
+    pub command: String,
+
+    // in seconds
+    pub interval: Option<u64>,
+
+    // the command runs again on SIGRTMIN+signal
+    pub signal: Option<i32>,
+
+    #[serde(default = "default_section")]
+    pub section: Section,
+}
+
+fn default_separator() -> String {
+    String::from(" | ")
+}
+
+fn default_section() -> Section {
+    Section::Right
+}
+
+impl BlocksConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::from_toml(&content)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(content).map_err(|err| err.to_string())?;
+
+        for block in &config.blocks {
+            if block.interval == Some(0) {
+                return Err(format!("block '{}': the interval can't be 0", block.name));
+            }
+
+            if let Some(signal) = block.signal {
+                let max = libc::SIGRTMAX() - libc::SIGRTMIN();
+                if !(0..=max).contains(&signal) {
+                    return Err(format!(
+                        "block '{}': the signal must be between 0 and {max}",
+                        block.name
+                    ));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+struct Block {
+    config: BlockConfig,
+    text: String,
+    reader: Option<LineReader<File>>,
+    child: Option<Child>,
+
+    // whether the current run printed anything, so a run that printed nothing clears the text
+    printed: bool,
+    next_run: Option<Instant>,
+}
+
+// runs the blocks as child processes, and composes their latest outputs into a single line.
+// the blocks use the tokens starting from the one they were given:
+// the timer, the signals, and then a token for each block's stdout
+pub struct Blocks {
+    separator: String,
+    blocks: Vec<Block>,
+    timer: TimerFd,
+    signals: SignalFd,
+    first_token: usize,
+    line: String,
+}
+
+impl Blocks {
+    pub fn new(config: BlocksConfig, registry: &Registry, first_token: Token) -> Self {
+        let now = Instant::now();
+        let blocks = config
+            .blocks
+            .into_iter()
+            .map(|config| Block {
+                config,
+                text: String::new(),
+                reader: None,
+                child: None,
+                printed: false,
+                next_run: Some(now),
+            })
+            .collect::<Vec<_>>();
+
+        // the signals have to be blocked to be read from a signalfd. the children don't inherit
+        // it, since std resets the signal mask when spawning.
+        let mask = signal_mask(blocks.iter().filter_map(|block| block.config.signal));
+        mask.thread_block()
+            .expect("can't block the blocks' signals");
+        let signals = SignalFd::with_flags(&mask, SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)
+            .expect("can't create a signalfd");
+
+        let timer = TimerFd::new(
+            ClockId::CLOCK_MONOTONIC,
+            TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC,
+        )
+        .expect("can't create a timerfd");
+
+        registry
+            .register(
+                &mut SourceFd(&timer.as_fd().as_raw_fd()),
+                first_token,
+                Interest::READABLE,
+            )
+            .expect("unable to register the blocks' timer");
+
+        registry
+            .register(
+                &mut SourceFd(&signals.as_raw_fd()),
+                Token(first_token.0 + 1),
+                Interest::READABLE,
+            )
+            .expect("unable to register the blocks' signals");
+
+        let mut blocks = Self {
+            separator: config.separator,
+            blocks,
+            timer,
+            signals,
+            first_token: first_token.0,
+            line: String::new(),
+        };
+
+        blocks.run_due(registry);
+        blocks
+    }
+
+    pub fn owns(&self, token: Token) -> bool {
+        let count = 2 + self.blocks.len();
+        (self.first_token..self.first_token + count).contains(&token.0)
+    }
+
+    // returns the new composed line, if it changed
+    pub fn handle(&mut self, token: Token, registry: &Registry) -> Option<String> {
+        match token.0 - self.first_token {
+            0 => {
+                // reading the timer only resets it, the deadlines are checked in `run_due`
+                let _ = self.timer.wait();
+                self.run_due(registry);
+            }
+
+            1 => {
+                while let Ok(Some(info)) = self.signals.read_signal() {
+                    let signal = info.ssi_signo as i32 - libc::SIGRTMIN();
+                    self.run_signaled(signal, registry);
+                }
+            }
+
+            index => self.read(index - 2, registry),
+        }
+
+        let line = self.compose();
+        if line == self.line {
+            return None;
+        }
+
+        self.line.clone_from(&line);
+        Some(line)
+    }
+
+    fn read(&mut self, index: usize, registry: &Registry) {
+        let Some(block) = self.blocks.get_mut(index) else {
+            return;
+        };
+
+        let Some(reader) = block.reader.as_mut() else {
+            return;
+        };
+
+        // a block that can't be read anymore is handled as if its output ended
+        let input = reader.read().unwrap_or_else(|err| {
+            eprintln!("WARN: can't read block '{}': {}", block.config.name, err);
+            Input {
+                line: None,
+                eof: true,
+            }
+        });
+
+        if let Some(line) = input.line {
+            block.text = line;
+            block.printed = true;
+        }
+
+        if input.eof {
+            // a closed fd would be reported as readable forever
+            let _ = registry.deregister(&mut SourceFd(&reader.as_raw_fd()));
+            block.reader = None;
+
+            if let Some(child) = block.child.take() {
+                reap(child);
+            }
+
+            if !block.printed {
+                block.text.clear();
+            }
+        }
+    }
+
+    fn run_due(&mut self, registry: &Registry) {
+        let now = Instant::now();
+
+        for index in 0..self.blocks.len() {
+            let block = &mut self.blocks[index];
+            if block.next_run.is_none_or(|at| at > now) {
+                continue;
+            }
+
+            block.next_run = block
+                .config
+                .interval
+                .map(|interval| now + Duration::from_secs(interval));
+
+            self.spawn(index, registry);
+        }
+
+        self.arm_timer(now);
+    }
+
+    fn run_signaled(&mut self, signal: i32, registry: &Registry) {
+        for index in 0..self.blocks.len() {
+            if self.blocks[index].config.signal == Some(signal) {
+                self.spawn(index, registry);
+            }
+        }
+    }
+
+    // wakes up on the closest interval
+    fn arm_timer(&self, now: Instant) {
+        let Some(next) = self.blocks.iter().filter_map(|block| block.next_run).min() else {
+            return;
+        };
+
+        // a zero expiration disarms the timer instead of firing right away
+        let delay = next
+            .saturating_duration_since(now)
+            .max(Duration::from_nanos(1));
+
+        self.timer
+            .set(
+                Expiration::OneShot(TimeSpec::from_duration(delay)),
+                TimerSetTimeFlags::empty(),
+            )
+            .expect("can't arm the blocks' timer");
+    }
+
+    fn spawn(&mut self, index: usize, registry: &Registry) {
+        let token = Token(self.first_token + 2 + index);
+        let block = &mut self.blocks[index];
+
+        // the previous run hasn't finished yet
+        if block.child.is_some() {
+            return;
+        }
+
+        let res = Command::new("sh")
+            .arg("-c")
+            .arg(&block.config.command)
+            .env("BLOCK_NAME", &block.config.name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn();
+
+        let mut child = match res {
+            Ok(child) => child,
+            Err(err) => {
+                eprintln!("WARN: can't run block '{}': {}", block.config.name, err);
+                return;
+            }
+        };
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let reader = LineReader::new(File::from(OwnedFd::from(stdout)))
+            .expect("can't open the block's stdout for non-blocking read");
+
+        registry
+            .register(
+                &mut SourceFd(&reader.as_raw_fd()),
+                token,
+                Interest::READABLE,
+            )
+            .expect("unable to register the block's stdout");
+
+        block.reader = Some(reader);
+        block.child = Some(child);
+        block.printed = false;
+    }
+
+    fn compose(&self) -> String {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| (block.config.section, block.text.as_str()));
+
+        compose(&self.separator, blocks)
+    }
+}
+
+impl Drop for Blocks {
+    fn drop(&mut self) {
+        for block in &mut self.blocks {
+            if let Some(mut child) = block.child.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+    }
+}
+
+// joins the texts of each section, skipping empty ones
+pub fn compose<'a>(
+    separator: &str,
+    blocks: impl Iterator<Item = (Section, &'a str)> + Clone,
+) -> String {
+    let mut line = String::new();
+
+    for (section, attribute) in [
+        (Section::Left, "%{l}"),
+        (Section::Center, "%{c}"),
+        (Section::Right, "%{r}"),
+    ] {
+        let texts = blocks
+            .clone()
+            .filter(|&(block_section, text)| block_section == section && !text.is_empty())
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>();
+
+        if texts.is_empty() {
+            continue;
+        }
+
+        line.push_str(attribute);
+        line.push_str(&texts.join(separator));
+    }
+
+    line
+}
+
+// nix only has the standard signals, so the realtime ones are added through libc
+fn signal_mask(signals: impl Iterator<Item = i32>) -> SigSet {
+    let mut set = MaybeUninit::<libc::sigset_t>::uninit();
+
+    // SAFETY: the set is initialized by sigemptyset before it's used, and the signals are
+    // validated to be realtime signals when the config is loaded
+    unsafe {
+        libc::sigemptyset(set.as_mut_ptr());
+        for signal in signals {
+            libc::sigaddset(set.as_mut_ptr(), libc::SIGRTMIN() + signal);
+        }
+
+        SigSet::from_sigset_t_unchecked(set.assume_init())
+    }
+}
+
+#[cfg(test)]
+mod tests;
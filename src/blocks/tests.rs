@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use super::{Blocks, BlocksConfig, compose};
+use crate::parser::Section;
+
+#[test]
+fn config_defaults() {
+    let config = BlocksConfig::from_toml(
+        r#"
+        [[block]]
+        name = "date"
+        command = "date +%H:%M"
+        interval = 5
+
+        [[block]]
+        name = "volume"
+        command = "pamixer --get-volume"
+        signal = 1
+        section = "left"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.separator, " | ");
+    assert_eq!(config.blocks.len(), 2);
+    assert_eq!(config.blocks[0].section, Section::Right);
+    assert_eq!(config.blocks[0].interval, Some(5));
+    assert_eq!(config.blocks[1].section, Section::Left);
+    assert_eq!(config.blocks[1].signal, Some(1));
+}
+
+#[test]
+fn config_errors() {
+    let zero_interval = "[[block]]\nname = \"a\"\ncommand = \"true\"\ninterval = 0";
+    assert!(BlocksConfig::from_toml(zero_interval).is_err());
+
+    let bad_signal = "[[block]]\nname = \"a\"\ncommand = \"true\"\nsignal = 1000";
+    assert!(BlocksConfig::from_toml(bad_signal).is_err());
+
+    let unknown_field = "[[block]]\nname = \"a\"\ncommand = \"true\"\ncolor = \"red\"";
+    assert!(BlocksConfig::from_toml(unknown_field).is_err());
+
+    let bad_section = "[[block]]\nname = \"a\"\ncommand = \"true\"\nsection = \"top\"";
+    assert!(BlocksConfig::from_toml(bad_section).is_err());
+}
+
+#[test]
+fn compose_sections() {
+    let blocks = [
+        (Section::Right, "cpu 3%"),
+        (Section::Left, "workspace 1"),
+        (Section::Right, ""),
+        (Section::Right, "12:00"),
+    ];
+
+    let line = compose(" | ", blocks.iter().copied());
+    assert_eq!(line, "%{l}workspace 1%{r}cpu 3% | 12:00");
+}
+
+#[test]
+fn compose_empty() {
+    let line = compose(" | ", [(Section::Center, "")].into_iter());
+    assert_eq!(line, "");
+}
+
+#[test]
+fn runs_blocks() {
+    let config = BlocksConfig::from_toml(
+        r#"
+        [[block]]
+        name = "hello"
+        command = "echo hello"
+        section = "left"
+
+        [[block]]
+        name = "name"
+        command = "echo $BLOCK_NAME"
+        interval = 60
+        "#,
+    )
+    .unwrap();
+
+    let mut poll = mio::Poll::new().unwrap();
+    let mut events = mio::Events::with_capacity(16);
+    let mut blocks = Blocks::new(config, poll.registry(), mio::Token(0));
+
+    let mut line = None;
+    while line.as_deref() != Some("%{l}hello%{r}name") {
+        poll.poll(&mut events, Some(Duration::from_secs(5)))
+            .unwrap();
+        assert!(!events.is_empty(), "timed out, last line: {line:?}");
+
+        for event in events.iter() {
+            if let Some(new_line) = blocks.handle(event.token(), poll.registry()) {
+                line = Some(new_line);
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt::Write as _;
+use std::os::fd::{AsFd, AsRawFd};
+
+use chrono::format::{Fixed, Item, Numeric, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token as PollToken};
+use nix::sys::time::TimeSpec;
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
+
+use crate::token::Token;
+
+// the clocks in the current line, which are redrawn on every second or minute boundary
+pub struct Clock {
+    timer: TimerFd,
+    texts: Vec<String>,
+}
+
+impl Clock {
+    pub fn new(registry: &Registry, token: PollToken) -> Self {
+        // the realtime clock is used, so the ticks are aligned with the displayed time
+        let timer = TimerFd::new(
+            ClockId::CLOCK_REALTIME,
+            TimerFlags::TFD_NONBLOCK | TimerFlags::TFD_CLOEXEC,
+        )
+        .expect("can't create a timerfd");
+
+        registry
+            .register(
+                &mut SourceFd(&timer.as_fd().as_raw_fd()),
+                token,
+                Interest::READABLE,
+            )
+            .expect("unable to register the clock's timer");
+
+        Self {
+            timer,
+            texts: Vec::new(),
+        }
+    }
+
+    // the formatted clocks, in the order they appear in the tokens
+    pub fn texts(&self) -> &[String] {
+        &self.texts
+    }
+
+    // resets the timer after it fired
+    pub fn tick(&self) {
+        let _ = self.timer.wait();
+    }
+
+    // formats the clocks and schedules the next tick, and returns whether the texts changed
+    pub fn update(&mut self, tokens: &[Token]) -> bool {
+        let now = Local::now();
+        let texts = format_all(tokens, now);
+
+        match period(tokens) {
+            Some(period) => {
+                // fires right on the next boundary, no matter how late this update is
+                let next = (Utc::now().timestamp() / period + 1) * period;
+                self.timer
+                    .set(
+                        Expiration::OneShot(TimeSpec::new(next, 0)),
+                        TimerSetTimeFlags::TFD_TIMER_ABSTIME,
+                    )
+                    .expect("can't arm the clock's timer");
+            }
+
+            // no clocks, so there's no need to wake up
+            None => {
+                let _ = self.timer.unset();
+            }
+        }
+
+        if texts == self.texts {
+            return false;
+        }
+
+        self.texts = texts;
+        true
+    }
+}
+
+// formats the time, where an invalid format shows nothing instead of panicking
+pub fn format(format: &str, now: DateTime<Local>) -> String {
+    let mut text = String::new();
+    if write!(text, "{}", now.format(format)).is_err() {
+        text.clear();
+    }
+
+    text
+}
+
+pub fn format_all(tokens: &[Token], now: DateTime<Local>) -> Vec<String> {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Clock(fmt) => Some(format(fmt, now)),
+            _ => None,
+        })
+        .collect()
+}
+
+// replaces the clocks with their formatted texts
+pub fn expand<'a>(tokens: &[Token<'a>], texts: &'a [String]) -> Vec<Token<'a>> {
+    let mut texts = texts.iter();
+
+    tokens
+        .iter()
+        .map(|token| match token {
+            Token::Clock(_) => Token::Text(texts.next().map_or("", String::as_str)),
+            token => *token,
+        })
+        .collect()
+}
+
+pub fn is_valid_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+// how often the clocks have to be redrawn in seconds, which is every second only when one of them
+// shows the seconds
+fn period(tokens: &[Token]) -> Option<i64> {
+    let mut formats = tokens
+        .iter()
+        .filter_map(|token| match token {
+            Token::Clock(format) => Some(*format),
+            _ => None,
+        })
+        .peekable();
+
+    formats.peek()?;
+
+    let has_seconds = formats.flat_map(StrftimeItems::new).any(|item| {
+        matches!(
+            item,
+            Item::Numeric(
+                Numeric::Second | Numeric::Nanosecond | Numeric::Timestamp,
+                _
+            ) | Item::Fixed(
+                Fixed::Nanosecond | Fixed::Nanosecond3 | Fixed::Nanosecond6 | Fixed::Nanosecond9
+            )
+        )
+    });
+
+    Some(if has_seconds { 1 } else { 60 })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,41 @@
+use chrono::{Local, TimeZone};
+
+use super::{expand, format_all, is_valid_format, period};
+use crate::parser;
+use crate::token::Token;
+
+#[test]
+fn formats_and_expands() {
+    let now = Local.with_ymd_and_hms(2024, 3, 9, 7, 5, 30).unwrap();
+    let tokens: Vec<_> = parser::parse("a %{clock:%H:%M} b %{clock:%d/%m}").collect();
+
+    let texts = format_all(&tokens, now);
+    assert_eq!(texts, ["07:05", "09/03"]);
+
+    let expanded = expand(&tokens, &texts);
+    assert!(matches!(
+        expanded.as_slice(),
+        [
+            Token::Text("a "),
+            Token::Text("07:05"),
+            Token::Text(" b "),
+            Token::Text("09/03"),
+        ]
+    ));
+}
+
+#[test]
+fn redraw_period() {
+    let period_of = |line| period(&parser::parse(line).collect::<Vec<_>>());
+
+    assert_eq!(period_of("no clock"), None);
+    assert_eq!(period_of("%{clock:%H:%M}"), Some(60));
+    assert_eq!(period_of("%{clock:%H:%M} %{clock:%T}"), Some(1));
+    assert_eq!(period_of("%{clock:%s}"), Some(1));
+}
+
+#[test]
+fn validates_format() {
+    assert!(is_valid_format("%Y-%m-%d %H:%M:%S"));
+    assert!(!is_valid_format("%Q"));
+}
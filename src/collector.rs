@@ -1,26 +1,45 @@
-use wayland_client::protocol::{wl_compositor, wl_registry, wl_shm};
+use wayland_client::protocol::{wl_compositor, wl_registry, wl_seat, wl_shm};
 use wayland_client::{Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::wp::fractional_scale::v1::client::wp_fractional_scale_manager_v1;
+use wayland_protocols::wp::viewporter::client::wp_viewporter;
 use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1;
 
 use crate::bar::Bar;
 use crate::config::Config;
 
+// the globals that the bar needs to create its surfaces
+pub struct Globals {
+    pub compositor: wl_compositor::WlCompositor,
+    pub shm: wl_shm::WlShm,
+    pub layer_shell: zwlr_layer_shell_v1::ZwlrLayerShellV1,
+
+    // both are needed for fractional scaling, and only the viewporter is used without it
+    pub viewporter: Option<wp_viewporter::WpViewporter>,
+    pub fractional_scale_manager:
+        Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
+}
+
 // TODO: looks similar to builder pattern. maybe automate?
 #[derive(Debug, Default)]
 pub struct Collector {
     compositor: Option<wl_compositor::WlCompositor>,
     shm: Option<wl_shm::WlShm>,
     layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    viewporter: Option<wp_viewporter::WpViewporter>,
+    fractional_scale_manager: Option<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1>,
 }
 
 impl Collector {
     pub fn collect(self, config: Config) -> Bar {
-        Bar::new(
-            self.compositor.expect("wl_compositor not found"),
-            self.shm.expect("wl_shm not found"),
-            self.layer_shell.expect("zwlr_layer_shell_v1 not found"),
-            config,
-        )
+        let globals = Globals {
+            compositor: self.compositor.expect("wl_compositor not found"),
+            shm: self.shm.expect("wl_shm not found"),
+            layer_shell: self.layer_shell.expect("zwlr_layer_shell_v1 not found"),
+            viewporter: self.viewporter,
+            fractional_scale_manager: self.fractional_scale_manager,
+        };
+
+        Bar::new(globals, config)
     }
 }
 
@@ -65,6 +84,32 @@ impl Dispatch<wl_registry::WlRegistry, QueueHandle<Bar>> for Collector {
                         Some(registry.bind::<wl_shm::WlShm, _, _>(name, version, state_qhandle, ()))
                 }
 
+                "wp_viewporter" => {
+                    state.viewporter = Some(registry.bind::<wp_viewporter::WpViewporter, _, _>(
+                        name,
+                        version.min(1),
+                        state_qhandle,
+                        (),
+                    ));
+                }
+
+                "wp_fractional_scale_manager_v1" => {
+                    state.fractional_scale_manager = Some(
+                        registry
+                            .bind::<wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, _, _>(
+                                name,
+                                version.min(1),
+                                state_qhandle,
+                                (),
+                            ),
+                    );
+                }
+
+                // the seat doesn't need to be kept, since the bar only reacts to its events
+                "wl_seat" => {
+                    registry.bind::<wl_seat::WlSeat, _, _>(name, version.min(7), state_qhandle, ());
+                }
+
                 _ => {}
             }
         }
@@ -1,14 +1,33 @@
-use clap::Parser;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io};
 
+use clap::parser::ValueSource;
+use clap::{ArgMatches, FromArgMatches as _, Parser, ValueEnum};
+use glob::Pattern;
+use serde::{Deserialize, Deserializer};
+
+use crate::parser::Size;
 use crate::pixels::Color;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 pub struct Config {
-    #[arg(long, short)]
+    /// same as --position top
+    #[arg(long, short, conflicts_with = "position")]
     pub top: bool,
 
+    /// the edge of the output that the bar is on
+    #[arg(long, value_enum, default_value_t = Position::Bottom)]
+    pub position: Position,
+
+    /// how the text is drawn on a vertical bar: rotated to read from top to bottom, or with the
+    /// characters stacked one below the other
+    #[arg(long, value_enum, default_value_t = VerticalText::Rotate)]
+    pub vertical_text: VerticalText,
+
+    /// font to use, can be repeated to fall back to the next font for missing glyphs
     #[arg(short, long)]
-    pub font: Option<String>,
+    pub font: Vec<String>,
 
     #[arg(long, short = 's', default_value_t = 24)]
     pub font_size: u32,
@@ -19,10 +38,517 @@ pub struct Config {
     #[arg(long, value_parser = parse_color, default_value = "000000")]
     pub bg: Color,
 
+    /// height of a horizontal bar, which is also the width of a vertical bar without a --width
     #[arg(long, default_value_t = 24)]
     pub height: u32,
+
+    /// width of a vertical bar, or of a horizontal bar that is centered by default instead of
+    /// spanning the whole output
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// which side of the output a bar with a --width is aligned to
+    #[arg(long, value_enum, default_value_t = Align::Center)]
+    pub align: Align,
+
+    /// space around the bar in pixels, as TOP,RIGHT,BOTTOM,LEFT or a single value for all sides
+    #[arg(long, value_name = "T,R,B,L", value_parser = parse_margin, default_value = "0")]
+    pub margin: Margin,
+
+    /// space that the windows leave for the bar: auto (its height), none, or a number of pixels
+    #[arg(long, value_parser = parse_exclusive_zone, default_value = "auto")]
+    pub exclusive_zone: ExclusiveZone,
+
+    /// the layer-shell layer that the bar is on
+    #[arg(long, value_enum, default_value_t = BarLayer::Top)]
+    pub layer: BarLayer,
+
+    /// print the commands of clicked %{A:...} areas to stdout
+    #[arg(long)]
+    pub clickable: bool,
+
+    /// height of the underline and overline, in pixels
+    #[arg(long, default_value_t = 2)]
+    pub underline_height: u32,
+
+    /// report malformed attributes on stderr, and don't draw the lines that contain them
+    #[arg(long)]
+    pub strict: bool,
+
+    /// validate the lines from stdin without drawing anything, exits with 1 if any line is invalid
+    #[arg(long)]
+    pub check: bool,
+
+    /// what to do when the input ends
+    #[arg(long, value_enum, default_value_t = OnEof::Exit)]
+    pub on_eof: OnEof,
+
+    /// only show the bar on outputs whose name or description matches the glob (repeatable)
+    #[arg(long = "output", value_name = "GLOB", value_parser = parse_pattern)]
+    pub outputs: Vec<Pattern>,
+
+    /// never show the bar on outputs whose name or description matches the glob (repeatable)
+    #[arg(long = "exclude-output", value_name = "GLOB", value_parser = parse_pattern)]
+    pub exclude_outputs: Vec<Pattern>,
+
+    /// render without a compositor into a WxH buffer, and write every frame to --dump-dir
+    #[arg(long, value_name = "WxH", value_parser = parse_size, requires = "dump_dir")]
+    pub headless: Option<Size>,
+
+    /// directory that the headless frames are written to
+    #[arg(long, value_name = "DIR", requires = "headless")]
+    pub dump_dir: Option<PathBuf>,
+
+    /// image format of the headless frames
+    #[arg(long, value_enum, default_value_t = DumpFormat::Png)]
+    pub dump_format: DumpFormat,
+
+    /// run the blocks described in this TOML file, instead of reading the lines from stdin
+    #[arg(long, value_name = "FILE", conflicts_with = "command")]
+    pub blocks: Option<PathBuf>,
+
+    /// name of this bar, used for the control socket at $XDG_RUNTIME_DIR/bread-NAME.sock
+    #[arg(long, default_value = "default")]
+    pub name: String,
+
+    /// config file, defaults to $XDG_CONFIG_HOME/bread/config.toml. the options given on the
+    /// command line take precedence over it
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// command to run and read the lines from, instead of reading them from stdin
+    #[arg(last = true, value_name = "COMMAND")]
+    pub command: Vec<String>,
+
+    // only comes from the config file
+    #[arg(skip)]
+    pub overrides: Vec<OutputOverride>,
 }
 
-fn parse_color(s: &str) -> Result<Color, &'static str> {
+impl Config {
+    // parses the command line, and fills in everything that wasn't given on it from the config file
+    pub fn load(matches: &ArgMatches) -> Result<Self, String> {
+        let config = Self::merged(matches)?;
+
+        // the compositor ignores the exclusive zone of a bar that's anchored to a corner
+        let cornered = !config.position.is_vertical()
+            && config.width.is_some()
+            && config.align != Align::Center;
+        if cornered && config.exclusive_zone != ExclusiveZone::None {
+            eprintln!(
+                "WARN: the exclusive zone is ignored for a bar with a width that's aligned to the left or to the right"
+            );
+        }
+
+        Ok(config)
+    }
+
+    fn merged(matches: &ArgMatches) -> Result<Self, String> {
+        let mut config = Self::from_arg_matches(matches).map_err(|err| err.to_string())?;
+
+        // --top can't be given together with --position
+        if config.top {
+            config.position = Position::Top;
+        }
+
+        let Some(path) = config.file_path() else {
+            return Ok(config);
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+
+            // only a config file that was given explicitly has to exist
+            Err(err) if err.kind() == io::ErrorKind::NotFound && config.config.is_none() => {
+                return Ok(config);
+            }
+
+            Err(err) => return Err(format!("can't read {}: {}", path.display(), err)),
+        };
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        config
+            .with_file(matches, &content, dir)
+            .map_err(|err| format!("invalid config {}: {}", path.display(), err))
+    }
+
+    // the size of the bar across the edge that it's on
+    pub fn thickness(&self) -> u32 {
+        if self.position.is_vertical() {
+            self.width.unwrap_or(self.height)
+        } else {
+            self.height
+        }
+    }
+
+    // the space that the windows leave for the bar, which is measured from the edge of the output,
+    // so it includes the margin on that edge
+    pub fn exclusive_pixels(&self) -> i32 {
+        let margin = match self.position {
+            Position::Top => self.margin.top,
+            Position::Bottom => self.margin.bottom,
+            Position::Left => self.margin.left,
+            Position::Right => self.margin.right,
+        };
+
+        match self.exclusive_zone {
+            ExclusiveZone::Auto => self.thickness() as i32 + margin,
+            ExclusiveZone::None => 0,
+            ExclusiveZone::Pixels(pixels) => pixels,
+        }
+    }
+
+    // the config file that should be used, which may not exist
+    pub fn file_path(&self) -> Option<PathBuf> {
+        if let Some(path) = &self.config {
+            return Some(path.clone());
+        }
+
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+        Some(config_home.join("bread").join("config.toml"))
+    }
+
+    // relative paths in the file are relative to the file's directory
+    fn with_file(
+        mut self,
+        matches: &ArgMatches,
+        content: &str,
+        dir: &Path,
+    ) -> Result<Self, String> {
+        let file: ConfigFile = toml::from_str(content).map_err(|err| err.to_string())?;
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {$(
+                if let Some(value) = file.$field
+                    && !from_cli(stringify!($field))
+                {
+                    self.$field = value;
+                }
+            )*};
+        }
+
+        merge!(
+            top,
+            position,
+            vertical_text,
+            font,
+            font_size,
+            fg,
+            bg,
+            height,
+            align,
+            margin,
+            exclusive_zone,
+            layer,
+            clickable,
+            underline_height,
+            strict,
+            on_eof,
+            outputs,
+            exclude_outputs,
+            name,
+            command,
+        );
+
+        // `top` is kept for compatibility, and only an explicit --position wins over it
+        if self.top && !from_cli("position") {
+            self.position = Position::Top;
+        }
+
+        if file.width.is_some() && !from_cli("width") {
+            self.width = file.width;
+        }
+
+        if let Some(blocks) = file.blocks
+            && !from_cli("blocks")
+        {
+            self.blocks = Some(dir.join(blocks));
+        }
+
+        self.overrides = file.overrides;
+        Ok(self)
+    }
+
+    // the config of a single output, with the overrides that match it applied in order
+    pub fn for_output(&self, name: &str, description: &str) -> Self {
+        let mut config = self.clone();
+
+        let overrides = self
+            .overrides
+            .iter()
+            .filter(|o| o.output.matches(name) || o.output.matches(description));
+
+        for output_override in overrides {
+            if let Some(top) = output_override.top {
+                config.position = if top { Position::Top } else { Position::Bottom };
+            }
+
+            config.position = output_override.position.unwrap_or(config.position);
+            config.height = output_override.height.unwrap_or(config.height);
+            config.fg = output_override.fg.unwrap_or(config.fg);
+            config.bg = output_override.bg.unwrap_or(config.bg);
+        }
+
+        config
+    }
+}
+
+// the options that the config file may contain, named the same as the command line options
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct ConfigFile {
+    top: Option<bool>,
+    position: Option<Position>,
+    vertical_text: Option<VerticalText>,
+    font: Option<Vec<String>>,
+    font_size: Option<u32>,
+
+    #[serde(deserialize_with = "color")]
+    fg: Option<Color>,
+
+    #[serde(deserialize_with = "color")]
+    bg: Option<Color>,
+
+    height: Option<u32>,
+    width: Option<u32>,
+    align: Option<Align>,
+
+    #[serde(deserialize_with = "margin")]
+    margin: Option<Margin>,
+
+    #[serde(deserialize_with = "exclusive_zone")]
+    exclusive_zone: Option<ExclusiveZone>,
+
+    layer: Option<BarLayer>,
+    clickable: Option<bool>,
+    underline_height: Option<u32>,
+    strict: Option<bool>,
+    on_eof: Option<OnEof>,
+
+    #[serde(rename = "output", deserialize_with = "patterns")]
+    outputs: Option<Vec<Pattern>>,
+
+    #[serde(rename = "exclude-output", deserialize_with = "patterns")]
+    exclude_outputs: Option<Vec<Pattern>>,
+
+    blocks: Option<PathBuf>,
+    name: Option<String>,
+    command: Option<Vec<String>>,
+
+    #[serde(rename = "override")]
+    overrides: Vec<OutputOverride>,
+}
+
+// changes the options of the outputs whose name or description matches the glob
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputOverride {
+    #[serde(deserialize_with = "pattern")]
+    pub output: Pattern,
+
+    pub top: Option<bool>,
+    pub position: Option<Position>,
+    pub height: Option<u32>,
+
+    #[serde(default, deserialize_with = "color")]
+    pub fg: Option<Color>,
+
+    #[serde(default, deserialize_with = "color")]
+    pub bg: Option<Color>,
+}
+
+// the values in the config file are parsed the same way as the command line arguments
+fn parse_with<'de, D, T, E>(deserializer: D, parse: fn(&str) -> Result<T, E>) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    E: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    parse(&s).map_err(serde::de::Error::custom)
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Color>, D::Error> {
+    parse_with(deserializer, parse_color).map(Some)
+}
+
+fn margin<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Margin>, D::Error> {
+    parse_with(deserializer, parse_margin).map(Some)
+}
+
+// either a number, or one of the names
+fn exclusive_zone<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<ExclusiveZone>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Pixels(i32),
+        Name(String),
+    }
+
+    match Value::deserialize(deserializer)? {
+        Value::Pixels(pixels) => Ok(Some(ExclusiveZone::Pixels(pixels))),
+        Value::Name(name) => parse_exclusive_zone(&name)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+fn pattern<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pattern, D::Error> {
+    parse_with(deserializer, parse_pattern)
+}
+
+fn patterns<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<Pattern>>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| parse_pattern(s).map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnEof {
+    /// close the bar
+    Exit,
+
+    /// keep showing the last line
+    Keep,
+
+    /// run the command again, with an increasing delay if it keeps exiting
+    Respawn,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Position {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl Position {
+    pub fn is_vertical(self) -> bool {
+        matches!(self, Self::Left | Self::Right)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalText {
+    Stack,
+    Rotate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Margin {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusiveZone {
+    // the height of the bar
+    Auto,
+
+    // the windows may go under the bar
+    None,
+
+    // negative values ask the compositor to not move the bar because of other exclusive zones
+    Pixels(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BarLayer {
+    Background,
+    Bottom,
+    Top,
+    Overlay,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum DumpFormat {
+    Png,
+    Ppm,
+}
+
+impl DumpFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            DumpFormat::Png => "png",
+            DumpFormat::Ppm => "ppm",
+        }
+    }
+}
+
+pub fn parse_color(s: &str) -> Result<Color, &'static str> {
     s.parse().map_err(|_| "invalid color")
 }
+
+fn parse_margin(s: &str) -> Result<Margin, &'static str> {
+    let values = s
+        .split(',')
+        .map(|value| value.trim().parse())
+        .collect::<Result<Vec<i32>, _>>()
+        .map_err(|_| "invalid margin, expected numbers")?;
+
+    match values[..] {
+        [all] => Ok(Margin {
+            top: all,
+            right: all,
+            bottom: all,
+            left: all,
+        }),
+        [top, right, bottom, left] => Ok(Margin {
+            top,
+            right,
+            bottom,
+            left,
+        }),
+        _ => Err("invalid margin, expected TOP,RIGHT,BOTTOM,LEFT or a single value"),
+    }
+}
+
+fn parse_exclusive_zone(s: &str) -> Result<ExclusiveZone, &'static str> {
+    match s {
+        "auto" => Ok(ExclusiveZone::Auto),
+        "none" => Ok(ExclusiveZone::None),
+        _ => s
+            .parse()
+            .map(ExclusiveZone::Pixels)
+            .map_err(|_| "invalid exclusive zone, expected auto, none or a number"),
+    }
+}
+
+fn parse_size(s: &str) -> Result<Size, &'static str> {
+    let size: Size = s.parse().map_err(|_| "invalid size, expected WxH")?;
+    if size.w == 0 || size.h == 0 {
+        return Err("the size can't be empty");
+    }
+
+    Ok(size)
+}
+
+fn parse_pattern(s: &str) -> Result<Pattern, String> {
+    Pattern::new(s).map_err(|err| format!("invalid glob: {err}"))
+}
+
+#[cfg(test)]
+mod tests;
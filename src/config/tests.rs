@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use clap::{CommandFactory as _, FromArgMatches as _};
+
+use super::{Align, Config, ExclusiveZone, Margin, OnEof, Position};
+
+fn load(args: &[&str], content: &str) -> Result<Config, String> {
+    let matches = Config::command().get_matches_from(args);
+    Config::load(&matches)?.with_file(&matches, content, Path::new("/etc/bread"))
+}
+
+#[test]
+fn file_fills_in_missing_args() {
+    let config = load(
+        &["bread", "--config", "/dev/null"],
+        r#"
+        font = ["Iosevka", "Symbols Nerd Font"]
+        font-size = 18
+        fg = "ff0000"
+        on-eof = "keep"
+        output = ["DP-*"]
+        blocks = "blocks.toml"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.font, ["Iosevka", "Symbols Nerd Font"]);
+    assert_eq!(config.font_size, 18);
+    assert_eq!(config.fg.r, 0xff);
+    assert_eq!(config.fg.g, 0x00);
+    assert_eq!(config.on_eof, OnEof::Keep);
+    assert!(config.outputs[0].matches("DP-2"));
+    assert_eq!(config.blocks.unwrap(), Path::new("/etc/bread/blocks.toml"));
+
+    // untouched options keep their defaults
+    assert_eq!(config.height, 24);
+}
+
+#[test]
+fn args_take_precedence() {
+    let config = load(
+        &[
+            "bread",
+            "--config",
+            "/dev/null",
+            "--height",
+            "30",
+            "--font",
+            "Mono",
+        ],
+        "height = 40\nfont = [\"Iosevka\"]\ntop = true",
+    )
+    .unwrap();
+
+    assert_eq!(config.height, 30);
+    assert_eq!(config.font, ["Mono"]);
+    assert_eq!(config.position, Position::Top);
+}
+
+#[test]
+fn invalid_files() {
+    let args = ["bread", "--config", "/dev/null"];
+
+    assert!(load(&args, "heigth = 40").is_err());
+    assert!(load(&args, "fg = \"red\"").is_err());
+    assert!(load(&args, "on-eof = \"never\"").is_err());
+    assert!(load(&args, "[[override]]\nheight = 30").is_err());
+}
+
+#[test]
+fn output_overrides() {
+    let config = load(
+        &["bread", "--config", "/dev/null", "--height", "30"],
+        r#"
+        [[override]]
+        output = "eDP-*"
+        height = 40
+        top = true
+
+        [[override]]
+        output = "*Dell*"
+        bg = "112233"
+        "#,
+    )
+    .unwrap();
+
+    let laptop = config.for_output("eDP-1", "Sharp panel");
+    assert_eq!(laptop.height, 40);
+    assert_eq!(laptop.position, Position::Top);
+    assert_eq!(laptop.bg.b, 0x00);
+
+    let monitor = config.for_output("DP-1", "Dell Inc. DELL U2419H");
+    assert_eq!(monitor.height, 30);
+    assert_eq!(monitor.position, Position::Bottom);
+    assert_eq!(monitor.bg.b, 0x33);
+}
+
+#[test]
+fn placement() {
+    let config = load(
+        &["bread", "--config", "/dev/null", "--margin", "4,8,0,8"],
+        r#"
+        width = 800
+        align = "right"
+        margin = "10"
+        exclusive-zone = 30
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.width, Some(800));
+    assert_eq!(config.align, Align::Right);
+    assert_eq!(
+        config.margin,
+        Margin {
+            top: 4,
+            right: 8,
+            bottom: 0,
+            left: 8
+        }
+    );
+    assert_eq!(config.exclusive_zone, ExclusiveZone::Pixels(30));
+
+    let config = load(
+        &["bread", "--config", "/dev/null", "--exclusive-zone", "none"],
+        "",
+    )
+    .unwrap();
+    assert_eq!(config.width, None);
+    assert_eq!(config.margin, Margin::default());
+    assert_eq!(config.exclusive_zone, ExclusiveZone::None);
+
+    assert!(load(&["bread", "--config", "/dev/null"], r#"margin = "1,2""#).is_err());
+    assert!(
+        load(
+            &["bread", "--config", "/dev/null"],
+            r#"exclusive-zone = "some""#
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn exclusive_zone_includes_the_margin() {
+    let config = load(
+        &["bread", "--config", "/dev/null", "--margin", "6,0,0,0"],
+        "",
+    )
+    .unwrap();
+    assert_eq!(config.exclusive_pixels(), 24);
+
+    let config = load(
+        &[
+            "bread",
+            "--config",
+            "/dev/null",
+            "--top",
+            "--margin",
+            "6,0,0,0",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(config.exclusive_pixels(), 30);
+
+    let config = load(
+        &[
+            "bread",
+            "--config",
+            "/dev/null",
+            "--position",
+            "left",
+            "--width",
+            "40",
+            "--margin",
+            "1,2,3,4",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(config.exclusive_pixels(), 44);
+
+    // only the automatic zone is made from the size of the bar
+    let config = load(
+        &[
+            "bread",
+            "--config",
+            "/dev/null",
+            "--exclusive-zone",
+            "30",
+            "--margin",
+            "6",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(config.exclusive_pixels(), 30);
+}
+
+#[test]
+fn position() {
+    let config = load(&["bread", "--config", "/dev/null", "--top"], "").unwrap();
+    assert_eq!(config.position, Position::Top);
+
+    // only an explicit --position wins over `top` in the file
+    let config = load(&["bread", "--config", "/dev/null"], "top = true").unwrap();
+    assert_eq!(config.position, Position::Top);
+
+    let config = load(
+        &["bread", "--config", "/dev/null", "--position", "left"],
+        "top = true",
+    )
+    .unwrap();
+    assert_eq!(config.position, Position::Left);
+
+    // a vertical bar is as wide as a horizontal one is high, unless its width is given
+    let config = load(
+        &["bread", "--config", "/dev/null", "--height", "30"],
+        r#"position = "right""#,
+    )
+    .unwrap();
+    assert_eq!(config.position, Position::Right);
+    assert_eq!(config.thickness(), 30);
+
+    let config = load(
+        &[
+            "bread",
+            "--config",
+            "/dev/null",
+            "--position",
+            "left",
+            "--width",
+            "40",
+        ],
+        "",
+    )
+    .unwrap();
+    assert_eq!(config.thickness(), 40);
+}
+
+#[test]
+fn headless_size() {
+    let args = |size| ["bread", "--headless", size, "--dump-dir", "/tmp"];
+
+    let matches = Config::command().get_matches_from(args("240x24"));
+    let config = Config::from_arg_matches(&matches).unwrap();
+    assert!(
+        config
+            .headless
+            .is_some_and(|size| size.w == 240 && size.h == 24)
+    );
+
+    for size in ["0x24", "240x0", "240"] {
+        assert!(Config::command().try_get_matches_from(args(size)).is_err());
+    }
+}
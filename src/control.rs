@@ -0,0 +1,257 @@
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::{env, fs};
+
+use clap::ValueEnum as _;
+use mio::net::{UnixListener, UnixStream};
+use mio::{Interest, Registry, Token};
+
+use crate::config::{BarLayer, Position, parse_color};
+use crate::pixels::Color;
+
+// a command that's sent to the control socket, one per line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Hide,
+    Show,
+    Toggle,
+    Height(u32),
+    Fg(Color),
+    Bg(Color),
+    Position(Position),
+    Layer(BarLayer),
+}
+
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<_> = line.split_whitespace().collect();
+
+    let command = match words.as_slice() {
+        ["hide"] => Command::Hide,
+        ["show"] => Command::Show,
+        ["toggle"] => Command::Toggle,
+
+        ["set", "height", height] => {
+            let height = height
+                .parse()
+                .map_err(|_| format!("invalid height '{height}'"))?;
+            Command::Height(height)
+        }
+
+        ["set", "fg", color] => Command::Fg(parse_color(color)?),
+        ["set", "bg", color] => Command::Bg(parse_color(color)?),
+
+        ["set", "position", position] => {
+            Command::Position(Position::from_str(position, true).map_err(|_| {
+                format!("invalid position '{position}', expected top, bottom, left or right")
+            })?)
+        }
+
+        ["layer", layer] => Command::Layer(BarLayer::from_str(layer, true).map_err(|_| {
+            format!("invalid layer '{layer}', expected background, bottom, top or overlay")
+        })?),
+
+        [] => return Err(String::from("empty command")),
+        _ => return Err(format!("unknown command '{line}'")),
+    };
+
+    Ok(command)
+}
+
+// the most clients that are connected at once, each using a token after the listener's one
+const MAX_CLIENTS: usize = 64;
+
+// the longest command that's accepted, clients that send longer lines are dropped
+const MAX_LINE: usize = 4096;
+
+struct Client {
+    stream: UnixStream,
+    buf: Vec<u8>,
+}
+
+// a unix socket that accepts line based commands, and answers each one with "ok" or "error: ..."
+// the listener uses the token it was given, and the clients use the MAX_CLIENTS tokens after it
+pub struct Control {
+    listener: UnixListener,
+    path: PathBuf,
+    first_token: usize,
+    clients: Vec<Option<Client>>,
+}
+
+impl Control {
+    // returns None when there's nowhere to put the socket, or when it's already used
+    pub fn new(name: &str, registry: &Registry, first_token: Token) -> Option<Self> {
+        let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR") else {
+            eprintln!("WARN: XDG_RUNTIME_DIR isn't set, there's no control socket");
+            return None;
+        };
+
+        let path = PathBuf::from(runtime_dir).join(format!("bread-{name}.sock"));
+
+        if path.exists() {
+            // a socket that nobody listens on is left over from a bar that crashed
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                eprintln!(
+                    "WARN: {} is used by another bar, use --name to give this one a different name",
+                    path.display()
+                );
+                return None;
+            }
+
+            let _ = fs::remove_file(&path);
+        }
+
+        let mut listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(err) => {
+                eprintln!("WARN: can't create {}: {}", path.display(), err);
+                return None;
+            }
+        };
+
+        registry
+            .register(&mut listener, first_token, Interest::READABLE)
+            .expect("unable to register the control socket");
+
+        Some(Self {
+            listener,
+            path,
+            first_token: first_token.0,
+            clients: Vec::new(),
+        })
+    }
+
+    pub fn owns(&self, token: Token) -> bool {
+        (self.first_token..=self.first_token + MAX_CLIENTS).contains(&token.0)
+    }
+
+    // returns the valid commands that were received, which were already answered with "ok"
+    pub fn handle(&mut self, token: Token, registry: &Registry) -> Vec<Command> {
+        match token.0 - self.first_token {
+            0 => {
+                self.accept(registry);
+                Vec::new()
+            }
+
+            index => self.read(index - 1, registry),
+        }
+    }
+
+    fn accept(&mut self, registry: &Registry) {
+        loop {
+            let mut stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) => {
+                    eprintln!("WARN: can't accept a control connection: {}", err);
+                    return;
+                }
+            };
+
+            // reuses the slots of disconnected clients, so the tokens stay small
+            let index = self
+                .clients
+                .iter()
+                .position(Option::is_none)
+                .unwrap_or(self.clients.len());
+
+            if index == MAX_CLIENTS {
+                eprintln!("WARN: too many control connections, dropping a new one");
+                continue;
+            }
+
+            let token = Token(self.first_token + 1 + index);
+            registry
+                .register(&mut stream, token, Interest::READABLE)
+                .expect("unable to register a control connection");
+
+            let client = Some(Client {
+                stream,
+                buf: Vec::new(),
+            });
+
+            if index == self.clients.len() {
+                self.clients.push(client);
+            } else {
+                self.clients[index] = client;
+            }
+        }
+    }
+
+    fn read(&mut self, index: usize, registry: &Registry) -> Vec<Command> {
+        let Some(Some(client)) = self.clients.get_mut(index) else {
+            return Vec::new();
+        };
+
+        let mut commands = Vec::new();
+        let mut closed = false;
+        let mut chunk = [0; 1024];
+        loop {
+            match client.stream.read(&mut chunk) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(len) => {
+                    client.buf.extend_from_slice(&chunk[..len]);
+                    client.answer(&mut commands);
+
+                    // only the unfinished line is left in the buffer
+                    if client.buf.len() > MAX_LINE {
+                        let _ = client.stream.write_all(b"error: command is too long\n");
+                        closed = true;
+                        break;
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => {
+                    closed = true;
+                    break;
+                }
+            }
+        }
+
+        // the last command doesn't have to end with a newline
+        if closed && !client.buf.is_empty() && client.buf.len() <= MAX_LINE {
+            client.buf.push(b'\n');
+            client.answer(&mut commands);
+        }
+
+        if closed {
+            let _ = registry.deregister(&mut client.stream);
+            self.clients[index] = None;
+        }
+
+        commands
+    }
+}
+
+impl Client {
+    // parses the finished lines in the buffer, and answers each of them
+    fn answer(&mut self, commands: &mut Vec<Command>) {
+        while let Some(end) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<_> = self.buf.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+
+            let response = match parse_command(line.trim()) {
+                Ok(command) => {
+                    commands.push(command);
+                    String::from("ok\n")
+                }
+                Err(err) => format!("error: {err}\n"),
+            };
+
+            // the responses are tiny, so a client that doesn't read them just misses them
+            let _ = self.stream.write_all(response.as_bytes());
+        }
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests;
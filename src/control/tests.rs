@@ -0,0 +1,36 @@
+use super::{Command, parse_command};
+use crate::config::{BarLayer, Position};
+use crate::pixels::Color;
+
+#[test]
+fn commands() {
+    assert_eq!(parse_command("hide"), Ok(Command::Hide));
+    assert_eq!(parse_command("  toggle "), Ok(Command::Toggle));
+    assert_eq!(parse_command("set height 30"), Ok(Command::Height(30)));
+    assert_eq!(
+        parse_command("set fg ff0000"),
+        Ok(Command::Fg(Color::new(0xff, 0x00, 0x00, 0xff)))
+    );
+    assert_eq!(
+        parse_command("set position bottom"),
+        Ok(Command::Position(Position::Bottom))
+    );
+    assert_eq!(
+        parse_command("set position left"),
+        Ok(Command::Position(Position::Left))
+    );
+    assert_eq!(
+        parse_command("layer overlay"),
+        Ok(Command::Layer(BarLayer::Overlay))
+    );
+}
+
+#[test]
+fn invalid_commands() {
+    assert!(parse_command("").is_err());
+    assert!(parse_command("hide now").is_err());
+    assert!(parse_command("set height tall").is_err());
+    assert!(parse_command("set fg red").is_err());
+    assert!(parse_command("set position middle").is_err());
+    assert!(parse_command("layer above").is_err());
+}
@@ -1,101 +1,186 @@
-use ab_glyph::{Font, PxScaleFont, ScaleFont as _, point};
+use std::ops::Range;
 
+use crate::font::Fonts;
 use crate::parser::Size;
-use crate::pixels::{Color, Pixels};
+use crate::pixels::Color;
+use crate::render::Style;
+
+// a clickable area, in pixels along the bar
+#[derive(Debug, Clone)]
+pub struct ActionRegion {
+    pub start: f32,
+    pub end: f32,
+    pub command: String,
+}
+
+impl ActionRegion {
+    pub fn contains(&self, x: f32) -> bool {
+        self.start <= x && x < self.end
+    }
+
+    pub fn width(&self) -> f32 {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Content {
+    Text(String),
+
+    // in the buffer's pixels
+    Ramp(Size),
+}
+
+// a part of the bar that is drawn on its own. it has everything that affects its pixels, so two
+// equal runs are drawn exactly the same.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Run {
+    pub start: f32,
+    pub width: f32,
+    pub content: Content,
+    pub fg: Color,
+    pub bg: Color,
+    pub font: Option<usize>,
+    pub line: Color,
+    pub underline: bool,
+    pub overline: bool,
+}
+
+impl Run {
+    pub fn end(&self) -> f32 {
+        self.start + self.width
+    }
+
+    // the whole pixels along the bar that the run covers
+    pub fn span(&self) -> Range<i64> {
+        self.start.floor() as i64..self.end().ceil() as i64
+    }
+}
 
-pub struct DrawState<'pixels, 'font, F: Font> {
-    pixels: &'pixels mut Pixels,
-    font: &'font PxScaleFont<F>,
+// follows the state changes of the tokens, and turns their text and ramps into runs, where x goes
+// along the bar
+pub struct DrawState<'fonts> {
+    fonts: &'fonts Fonts,
+    style: Style,
     x: f32,
     fg: Color,
     bg: Color,
+
+    // index of the forced font, if any
+    font: Option<usize>,
+
+    // underline/overline
+    line: Color,
+    underline: bool,
+    overline: bool,
+
+    // started action areas, along with their starting x
+    open_actions: Vec<(f32, String)>,
+    actions: Vec<ActionRegion>,
+    runs: Vec<Run>,
 }
 
-impl<'pixels, 'font, F: Font> DrawState<'pixels, 'font, F> {
-    pub fn new(
-        pixels: &'pixels mut Pixels,
-        font: &'font PxScaleFont<F>,
-        start_x: f32,
-        fg: Color,
-        bg: Color,
-    ) -> Self {
+impl<'fonts> DrawState<'fonts> {
+    pub fn new(fonts: &'fonts Fonts, start_x: f32, style: &Style) -> Self {
         let x = start_x;
 
         Self {
-            pixels,
-            font,
+            fonts,
+            style: *style,
             x,
-            fg,
-            bg,
+            fg: style.fg,
+            bg: style.bg,
+            font: None,
+            line: style.fg,
+            underline: false,
+            overline: false,
+            open_actions: Vec::new(),
+            actions: Vec::new(),
+            runs: Vec::new(),
         }
     }
 
-    pub fn set_fg(&mut self, fg: Color) {
-        self.fg = fg;
+    pub fn start_action(&mut self, command: &str) {
+        self.open_actions.push((self.x, command.to_string()));
     }
 
-    pub fn set_bg(&mut self, bg: Color) {
-        self.bg = bg;
-    }
+    pub fn end_action(&mut self) {
+        let Some((start, command)) = self.open_actions.pop() else {
+            return;
+        };
 
-    pub fn draw_text(&mut self, text: &str) {
-        let center_y = (self.pixels.height() as f32 - self.font.height()) / 2.;
-        let center_y = center_y as i32;
+        self.actions.push(ActionRegion {
+            start,
+            end: self.x,
+            command,
+        });
+    }
 
-        for c in text.chars() {
-            let mut glyph = self.font.scaled_glyph(c);
-            glyph.position = point(self.x, 0.);
+    // closes all of the unclosed action areas, and returns the runs and the areas
+    pub fn finish(mut self) -> (Vec<Run>, Vec<ActionRegion>) {
+        while !self.open_actions.is_empty() {
+            self.end_action();
+        }
 
-            let h_advance = self.font.h_advance(glyph.id);
+        (self.runs, self.actions)
+    }
 
-            // fill background
-            for off_x in 0..h_advance.ceil() as u32 {
-                for y in 0..self.pixels.height() {
-                    let x = self.x as u32 + off_x;
-                    self.pixels.set(x, y, self.bg);
-                }
-            }
+    pub fn set_fg(&mut self, fg: Color) {
+        self.fg = fg;
+    }
 
-            let Some(outline) = self.font.outline_glyph(glyph) else {
-                self.x += h_advance;
-                continue;
-            };
+    pub fn set_bg(&mut self, bg: Color) {
+        self.bg = bg;
+    }
 
-            let bounds = outline.px_bounds();
+    pub fn set_font(&mut self, font: Option<usize>) {
+        self.font = font;
+    }
 
-            outline.draw(|x, y, f| {
-                let x = bounds.min.x as i32 + x as i32;
-                let y = center_y + bounds.min.y as i32 + y as i32 + self.font.ascent() as i32;
-                if x < 0 || y < 0 {
-                    return;
-                }
+    pub fn set_line(&mut self, line: Color) {
+        self.line = line;
+    }
 
-                let x = x as u32;
-                let y = y as u32;
+    pub fn set_underline(&mut self, underline: bool) {
+        self.underline = underline;
+    }
 
-                let f = f.clamp(0., 1.);
-                let color = self.bg.interpolate(self.fg, f);
+    pub fn set_overline(&mut self, overline: bool) {
+        self.overline = overline;
+    }
 
-                self.pixels.set(x, y, color);
-            });
+    pub fn add_text(&mut self, text: &str) {
+        let width = if self.style.is_stacked() {
+            self.fonts.stack(text, self.font).width
+        } else {
+            self.fonts.text_width(text, self.font)
+        };
 
-            self.x += h_advance;
-        }
+        self.add(Content::Text(text.to_string()), width);
     }
 
-    pub fn draw_ramp(&mut self, size: Size) {
-        let max_y = self.pixels.height() - size.h;
-        for x in 0..size.w {
-            let x = self.x.ceil() as u32 + x;
-            for y in 0..max_y {
-                self.pixels.set(x, y, self.bg);
-            }
+    pub fn add_ramp(&mut self, size: Size) {
+        let size = Size {
+            w: self.style.scaled(size.w),
+            h: self.style.scaled(size.h),
+        };
 
-            for y in max_y..self.pixels.height() {
-                self.pixels.set(x, y, self.fg);
-            }
-        }
+        self.add(Content::Ramp(size), size.w as f32);
+    }
 
-        self.x += size.w as f32;
+    fn add(&mut self, content: Content, width: f32) {
+        self.runs.push(Run {
+            start: self.x,
+            width,
+            content,
+            fg: self.fg,
+            bg: self.bg,
+            font: self.font,
+            line: self.line,
+            underline: self.underline,
+            overline: self.overline,
+        });
+
+        self.x += width;
     }
 }
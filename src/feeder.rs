@@ -0,0 +1,210 @@
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mio::unix::SourceFd;
+use mio::{Interest, Registry};
+
+use crate::config::{Config, OnEof};
+use crate::line_reader::{Input, LineReader};
+
+// the first respawn is delayed by this much, and each quick exit after it doubles the delay
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+// a command that runs for at least this long is considered healthy, and resets the backoff
+const HEALTHY_RUNTIME: Duration = Duration::from_secs(10);
+
+// how long a command that closed its stdout has to exit on its own before it's killed
+const REAP_GRACE: Duration = Duration::from_millis(100);
+
+// the program that generates the lines, which is either whatever is piped into stdin or a command
+// that bread runs (and supervises) on its own
+pub struct Feeder {
+    command: Vec<String>,
+    on_eof: OnEof,
+    token: mio::Token,
+    reader: Option<LineReader<File>>,
+    child: Option<Child>,
+    spawned_at: Instant,
+    backoff: Duration,
+    respawn_at: Option<Instant>,
+    finished: bool,
+}
+
+impl Feeder {
+    pub fn new(config: &Config, token: mio::Token) -> Self {
+        let mut feeder = Self {
+            command: config.command.clone(),
+            on_eof: config.on_eof,
+            token,
+            reader: None,
+            child: None,
+            spawned_at: Instant::now(),
+            backoff: MIN_BACKOFF,
+            respawn_at: None,
+            finished: false,
+        };
+
+        if feeder.command.is_empty() {
+            // both stdin and the command's stdout are read as plain files
+            let stdin = io::stdin()
+                .as_fd()
+                .try_clone_to_owned()
+                .expect("can't duplicate stdin");
+            let reader =
+                LineReader::new(File::from(stdin)).expect("can't open stdin for non-blocking read");
+            feeder.reader = Some(reader);
+        } else {
+            feeder.spawn();
+        }
+
+        feeder
+    }
+
+    // whether the input has ended, and the bar should exit
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn register(&self, registry: &Registry) {
+        let Some(reader) = &self.reader else {
+            return;
+        };
+
+        registry
+            .register(
+                &mut SourceFd(&reader.as_raw_fd()),
+                self.token,
+                Interest::READABLE,
+            )
+            .expect("unable to register the input");
+    }
+
+    // the time left until the command should be respawned, used as the polling timeout
+    pub fn timeout(&self) -> Option<Duration> {
+        self.respawn_at
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    pub fn respawn_if_due(&mut self, registry: &Registry) {
+        if self.respawn_at.is_some_and(|at| at <= Instant::now()) {
+            self.respawn_at = None;
+            self.spawn();
+            self.register(registry);
+        }
+    }
+
+    // returns the newest complete line, if any
+    pub fn read(&mut self, registry: &Registry) -> Option<String> {
+        let reader = self.reader.as_mut()?;
+
+        // the input can't be read anymore, which is the same as its end
+        let input = reader.read().unwrap_or_else(|err| {
+            eprintln!("WARN: can't read the input: {}", err);
+            Input {
+                line: None,
+                eof: true,
+            }
+        });
+
+        if input.eof {
+            self.on_eof(registry);
+        }
+
+        input.line
+    }
+
+    fn on_eof(&mut self, registry: &Registry) {
+        // a closed fd would be reported as readable forever
+        if let Some(reader) = self.reader.take() {
+            let _ = registry.deregister(&mut SourceFd(&reader.as_raw_fd()));
+        }
+
+        self.handle_end();
+    }
+
+    fn handle_end(&mut self) {
+        if let Some(child) = self.child.take() {
+            reap(child);
+        }
+
+        match self.on_eof {
+            OnEof::Exit => self.finished = true,
+
+            // the last frame stays on the screen
+            OnEof::Keep => {}
+
+            OnEof::Respawn => {
+                if self.spawned_at.elapsed() >= HEALTHY_RUNTIME {
+                    self.backoff = MIN_BACKOFF;
+                }
+
+                eprintln!("WARN: the command exited, respawning in {:?}", self.backoff);
+                self.respawn_at = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+
+    fn spawn(&mut self) {
+        let (program, args) = self
+            .command
+            .split_first()
+            .expect("the command shouldn't be empty");
+
+        self.spawned_at = Instant::now();
+
+        let res = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn();
+
+        let mut child = match res {
+            Ok(child) => child,
+            Err(err) => {
+                eprintln!("ERROR: can't run '{}': {}", program, err);
+
+                // failing to spawn is treated the same as exiting right away
+                self.handle_end();
+                return;
+            }
+        };
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let reader = LineReader::new(File::from(OwnedFd::from(stdout)))
+            .expect("can't open the command's stdout for non-blocking read");
+
+        self.reader = Some(reader);
+        self.child = Some(child);
+    }
+}
+
+// waits for a child that closed its stdout, which usually exits right after it. a child that keeps
+// running without it (e.g. a script that daemonizes) is killed after a short while, since waiting
+// for it would block the whole bar.
+pub fn reap(mut child: Child) {
+    let deadline = Instant::now() + REAP_GRACE;
+    while let Ok(None) = child.try_wait() {
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return;
+        }
+
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+impl Drop for Feeder {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
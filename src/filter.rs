@@ -0,0 +1,29 @@
+use glob::Pattern;
+
+use crate::config::Config;
+
+// decides which wl_outputs get a bar, based on their name (e.g. "DP-1") and description (e.g.
+// "Dell Inc. DELL U2419H ...")
+pub struct OutputFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl OutputFilter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            include: config.outputs.clone(),
+            exclude: config.exclude_outputs.clone(),
+        }
+    }
+
+    pub fn matches(&self, name: &str, description: &str) -> bool {
+        let is_match = |pattern: &Pattern| pattern.matches(name) || pattern.matches(description);
+
+        // no --output means every output is included
+        let included = self.include.is_empty() || self.include.iter().any(is_match);
+        let excluded = self.exclude.iter().any(is_match);
+
+        included && !excluded
+    }
+}
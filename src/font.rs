@@ -0,0 +1,335 @@
+use std::ops::Range;
+
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, PxScaleFont, ScaleFont as _};
+use rust_fontconfig::{FcFontCache, FcPattern};
+use rustybuzz::ttf_parser::Tag;
+use rustybuzz::ttf_parser::gpos::PositioningSubtable;
+use rustybuzz::ttf_parser::gsub::SubstitutionSubtable;
+use rustybuzz::ttf_parser::opentype_layout::{Coverage, LayoutTable, Lookup};
+use rustybuzz::{Direction, UnicodeBuffer};
+use unicode_bidi::{BidiClass, ParagraphBidiInfo};
+
+// the features that are applied to plain text by default, and may replace its glyphs (ligatures)
+// or move them (kerning)
+const PLAIN_TEXT_FEATURES: [Tag; 5] = [
+    Tag::from_bytes(b"liga"),
+    Tag::from_bytes(b"clig"),
+    Tag::from_bytes(b"calt"),
+    Tag::from_bytes(b"rlig"),
+    Tag::from_bytes(b"kern"),
+];
+
+struct Font {
+    scaled: PxScaleFont<FontVec>,
+
+    // whether the font may change the glyphs of plain text or their positions. without it, the
+    // text can be laid out by simply advancing char-by-char.
+    layout_tables: bool,
+}
+
+impl Font {
+    fn new(font: FontVec, scale: PxScale) -> Self {
+        let scaled = font.into_scaled(scale);
+        let layout_tables = face(&scaled).is_some_and(|face| changes_plain_text(&face));
+
+        Self {
+            scaled,
+            layout_tables,
+        }
+    }
+}
+
+// the font for shaping, which is cheap to parse since its tables are only read when they're used.
+// fonts are always loaded from the first face in the file.
+fn face(font: &PxScaleFont<FontVec>) -> Option<rustybuzz::Face<'_>> {
+    rustybuzz::Face::from_slice(font.font().as_slice(), 0)
+}
+
+fn changes_plain_text(face: &rustybuzz::Face) -> bool {
+    let ascii: Vec<_> = (' '..='~').filter_map(|c| face.glyph_index(c)).collect();
+
+    // whether a lookup of the features starts at any of the ascii glyphs
+    let applies = |table: Option<LayoutTable>, starts: &dyn Fn(Lookup) -> bool| {
+        table.is_some_and(|table| {
+            table
+                .features
+                .into_iter()
+                .filter(|feature| PLAIN_TEXT_FEATURES.contains(&feature.tag))
+                .flat_map(|feature| feature.lookup_indices)
+                .filter_map(|index| table.lookups.get(index))
+                .any(starts)
+        })
+    };
+    let covers = |coverage: Coverage| ascii.iter().any(|id| coverage.contains(*id));
+
+    let tables = face.tables();
+    applies(tables.gsub, &|lookup| {
+        lookup
+            .subtables
+            .into_iter::<SubstitutionSubtable>()
+            .any(|subtable| covers(subtable.coverage()))
+    }) || applies(tables.gpos, &|lookup| {
+        lookup
+            .subtables
+            .into_iter::<PositioningSubtable>()
+            .any(|subtable| covers(subtable.coverage()))
+    }) || tables.kern.is_some_and(|kern| !kern.subtables.is_empty())
+        || tables.morx.is_some()
+}
+
+// a glyph positioned relative to the start of the text it was shaped from
+#[derive(Debug, Clone, Copy)]
+pub struct ShapedGlyph {
+    pub font: usize,
+    pub id: GlyphId,
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct ShapedText {
+    pub glyphs: Vec<ShapedGlyph>,
+    pub width: f32,
+}
+
+// an ordered list of fonts, where each character is drawn using the first font that has it
+pub struct Fonts {
+    fonts: Vec<Font>,
+
+    // in pixels
+    size: u32,
+}
+
+impl Fonts {
+    pub fn load(names: &[String], size: u32) -> Self {
+        let fc = FcFontCache::build();
+
+        let patterns: Vec<_> = if names.is_empty() {
+            vec![FcPattern::default()]
+        } else {
+            names
+                .iter()
+                .map(|name| FcPattern {
+                    name: Some(name.clone()),
+                    ..Default::default()
+                })
+                .collect()
+        };
+
+        let fonts = patterns
+            .into_iter()
+            .map(|pattern| {
+                let Some(m) = fc.query(&pattern, &mut Vec::new()) else {
+                    if let Some(name) = pattern.name {
+                        eprintln!("ERROR: no such font '{}'", name);
+                    } else {
+                        eprintln!("ERROR: no font available");
+                    }
+
+                    std::process::exit(1);
+                };
+
+                fc.get_font_bytes(&m.id).expect("font should be accessible")
+            })
+            .collect();
+
+        Self::from_data(fonts, size)
+    }
+
+    // creates the fonts from the content of font files, in fallback order
+    pub fn from_data(fonts: Vec<Vec<u8>>, size: u32) -> Self {
+        let scale = PxScale::from(size as f32);
+        let fonts = fonts
+            .into_iter()
+            .map(|data| Font::new(FontVec::try_from_vec(data).unwrap(), scale))
+            .collect();
+
+        Self { fonts, size }
+    }
+
+    // the same fonts at another size, for outputs with a different scale
+    pub fn resized(&self, size: u32) -> Self {
+        let fonts = self
+            .fonts
+            .iter()
+            .map(|font| font.scaled.font().as_slice().to_vec())
+            .collect();
+
+        Self::from_data(fonts, size)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn get(&self, index: usize) -> &PxScaleFont<FontVec> {
+        &self.fonts[index].scaled
+    }
+
+    // index of the font that should be used for the given character.
+    // `forced` is an index of a font that should be used no matter what.
+    fn index_for_char(&self, c: char, forced: Option<usize>) -> usize {
+        if let Some(index) = forced.filter(|&index| index < self.fonts.len()) {
+            return index;
+        }
+
+        self.fonts
+            .iter()
+            .position(|font| font.scaled.glyph_id(c).0 != 0)
+            .unwrap_or(0)
+    }
+
+    pub fn text_width(&self, text: &str, forced: Option<usize>) -> f32 {
+        if !self.needs_shaping(text, forced) {
+            return text
+                .chars()
+                .map(|c| {
+                    let font = self.get(self.index_for_char(c, forced));
+                    font.h_advance(font.glyph_id(c))
+                })
+                .sum();
+        }
+
+        self.shape(text, forced).width
+    }
+
+    // turns the text into positioned glyphs, in visual order (left to right)
+    pub fn shape(&self, text: &str, forced: Option<usize>) -> ShapedText {
+        let mut shaped = ShapedText::default();
+
+        if !self.needs_shaping(text, forced) {
+            for c in text.chars() {
+                let index = self.index_for_char(c, forced);
+                let font = self.get(index);
+                let id = font.glyph_id(c);
+
+                shaped.glyphs.push(ShapedGlyph {
+                    font: index,
+                    id,
+                    x: shaped.width,
+                    y: 0.,
+                });
+                shaped.width += font.h_advance(id);
+            }
+
+            return shaped;
+        }
+
+        // reorder right-to-left text into runs that are in visual order
+        let bidi = ParagraphBidiInfo::new(text, None);
+        let (levels, runs) = bidi.visual_runs(0..text.len());
+
+        for run in runs {
+            let rtl = levels[run.start].is_rtl();
+
+            // each run is split into parts that use the same font, since shaping is per font
+            let mut segments = self.font_segments(text, run, forced);
+            if rtl {
+                segments.reverse();
+            }
+
+            for (font, range) in segments {
+                self.shape_segment(&text[range], font, rtl, &mut shaped);
+            }
+        }
+
+        shaped
+    }
+
+    // puts the characters one below the other, each one upright, for the stacked text of vertical
+    // bars. the y of each glyph is the top of its line, and the width is the height of all lines.
+    pub fn stack(&self, text: &str, forced: Option<usize>) -> ShapedText {
+        let mut stacked = ShapedText::default();
+
+        for c in text.chars() {
+            let index = self.index_for_char(c, forced);
+            let font = self.get(index);
+
+            stacked.glyphs.push(ShapedGlyph {
+                font: index,
+                id: font.glyph_id(c),
+                x: 0.,
+                y: stacked.width,
+            });
+            stacked.width += font.height();
+        }
+
+        stacked
+    }
+
+    // plain ascii that doesn't go through fonts with layout tables is the common case, and
+    // doesn't need the full shaping machinery
+    fn needs_shaping(&self, text: &str, forced: Option<usize>) -> bool {
+        !text.is_ascii()
+            || text
+                .chars()
+                .any(|c| self.fonts[self.index_for_char(c, forced)].layout_tables)
+    }
+
+    fn font_segments(
+        &self,
+        text: &str,
+        range: Range<usize>,
+        forced: Option<usize>,
+    ) -> Vec<(usize, Range<usize>)> {
+        let mut segments: Vec<(usize, Range<usize>)> = Vec::new();
+
+        for (offset, c) in text[range.clone()].char_indices() {
+            let start = range.start + offset;
+            let end = start + c.len_utf8();
+
+            // combining marks stay with their base character, so that they are shaped together
+            let index = match segments.last() {
+                Some(&(font, _)) if is_mark(c) => font,
+                _ => self.index_for_char(c, forced),
+            };
+
+            match segments.last_mut() {
+                Some((font, segment)) if *font == index => segment.end = end,
+                _ => segments.push((index, start..end)),
+            }
+        }
+
+        segments
+    }
+
+    fn shape_segment(&self, text: &str, index: usize, rtl: bool, shaped: &mut ShapedText) {
+        let font = &self.fonts[index].scaled;
+        let Some(face) = face(font) else {
+            return;
+        };
+
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        buffer.set_direction(if rtl {
+            Direction::RightToLeft
+        } else {
+            Direction::LeftToRight
+        });
+
+        let output = rustybuzz::shape(&face, &[], buffer);
+
+        // rustybuzz works in font units, with the y axis going up
+        let scale_x = font.h_scale_factor();
+        let scale_y = font.v_scale_factor();
+
+        for (info, position) in output.glyph_infos().iter().zip(output.glyph_positions()) {
+            shaped.glyphs.push(ShapedGlyph {
+                font: index,
+                id: GlyphId(info.glyph_id as u16),
+                x: shaped.width + position.x_offset as f32 * scale_x,
+                y: -position.y_offset as f32 * scale_y,
+            });
+
+            shaped.width += position.x_advance as f32 * scale_x;
+        }
+    }
+}
+
+fn is_mark(c: char) -> bool {
+    unicode_bidi::bidi_class(c) == BidiClass::NSM
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,31 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::Fonts;
+
+fn fonts() -> Fonts {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fonts/DejaVuSansMono.ttf");
+    Fonts::from_data(vec![fs::read(path).unwrap()], 16)
+}
+
+#[test]
+fn plain_text_skips_shaping() {
+    let fonts = fonts();
+
+    // the font has layout tables, but none that change plain text
+    assert!(super::face(fonts.get(0)).is_some());
+    assert!(!fonts.needs_shaping("12:34 Hello, world!", None));
+    assert!(fonts.needs_shaping("שלום", None));
+}
+
+#[test]
+fn shaping_matches_the_fast_path() {
+    let fonts = fonts();
+    let text = "Hello, world!";
+
+    let mut shaped = super::ShapedText::default();
+    fonts.shape_segment(text, 0, false, &mut shaped);
+
+    assert_eq!(shaped.glyphs.len(), text.len());
+    assert!((shaped.width - fonts.text_width(text, None)).abs() < 0.01);
+}
@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+
+use ab_glyph::{GlyphId, ScaleFont as _, point};
+
+use crate::font::Fonts;
+
+// the glyphs are placed at fractions of a pixel, which are rounded to this many steps along x.
+// each step is rasterized on its own, so text that starts in the middle of a pixel isn't shifted.
+// the offsets along y are kept exact, since they're the same for most of the glyphs.
+const SUBPIXEL_STEPS: f32 = 4.;
+
+// the most glyphs that are kept, which is plenty for the few characters a bar usually shows
+const CAPACITY: usize = 2048;
+
+// the coverage of a rasterized glyph, relative to the whole pixel it's placed at
+pub struct Glyph {
+    pub left: i64,
+    pub top: i64,
+    pub width: u32,
+    pub height: u32,
+
+    // row by row, where 255 is fully covered
+    pub coverage: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    size: u32,
+    font: usize,
+    id: GlyphId,
+
+    // the steps of the subpixel offset along x, and the bits of the exact offset along y
+    x: u8,
+    y: u32,
+}
+
+struct Entry {
+    // glyphs without an outline (e.g. spaces) are kept as well, so they aren't looked up again
+    glyph: Option<Rc<Glyph>>,
+    used: u64,
+}
+
+// rasterized glyphs of every font and size, so the same characters are only rasterized once. the
+// least recently used glyphs are dropped when it's full.
+pub struct GlyphCache {
+    entries: HashMap<Key, Entry>,
+    capacity: usize,
+
+    // the glyphs by when they were used last, so the oldest one is found right away
+    order: BTreeMap<u64, Key>,
+
+    // increased on every lookup, to know which glyphs were used last
+    clock: u64,
+}
+
+impl Default for GlyphCache {
+    fn default() -> Self {
+        Self::with_capacity(CAPACITY)
+    }
+}
+
+impl GlyphCache {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            capacity: capacity.max(1),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    // has to be called when the fonts are replaced by others of the same size
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    // the glyph of the font at the position, along with the whole pixel that it's relative to
+    pub fn get(
+        &mut self,
+        fonts: &Fonts,
+        font: usize,
+        id: GlyphId,
+        x: f32,
+        y: f32,
+    ) -> Option<(i64, i64, Rc<Glyph>)> {
+        let (x, step_x) = split(x);
+        let whole_y = y.floor();
+        let key = Key {
+            size: fonts.size(),
+            font,
+            id,
+            x: step_x,
+            y: (y - whole_y).to_bits(),
+        };
+        let y = whole_y as i64;
+
+        self.clock += 1;
+
+        let glyph = match self.entries.get_mut(&key) {
+            Some(entry) => {
+                self.order.remove(&entry.used);
+                self.order.insert(self.clock, key);
+                entry.used = self.clock;
+                entry.glyph.clone()
+            }
+            None => {
+                let glyph = rasterize(fonts, key).map(Rc::new);
+                self.insert(key, glyph.clone());
+                glyph
+            }
+        };
+
+        glyph.map(|glyph| (x, y, glyph))
+    }
+
+    fn insert(&mut self, key: Key, glyph: Option<Rc<Glyph>>) {
+        if self.entries.len() >= self.capacity
+            && let Some((_, oldest)) = self.order.pop_first()
+        {
+            self.entries.remove(&oldest);
+        }
+
+        let used = self.clock;
+        self.order.insert(used, key);
+        self.entries.insert(key, Entry { glyph, used });
+    }
+}
+
+// splits a position into the whole pixel and the step of the subpixel offset within it
+fn split(position: f32) -> (i64, u8) {
+    let steps = (position * SUBPIXEL_STEPS).round();
+    let whole = (steps / SUBPIXEL_STEPS).floor();
+    (whole as i64, (steps - whole * SUBPIXEL_STEPS) as u8)
+}
+
+fn rasterize(fonts: &Fonts, key: Key) -> Option<Glyph> {
+    let font = fonts.get(key.font);
+    let offset = point(key.x as f32 / SUBPIXEL_STEPS, f32::from_bits(key.y));
+
+    let outline = font.outline_glyph(key.id.with_scale_and_position(font.scale(), offset))?;
+    let bounds = outline.px_bounds();
+
+    let width = bounds.width() as u32;
+    let height = bounds.height() as u32;
+    let mut coverage = vec![0; (width * height) as usize];
+
+    outline.draw(|x, y, f| {
+        let index = (y * width + x) as usize;
+        if let Some(pixel) = coverage.get_mut(index) {
+            *pixel = (f.clamp(0., 1.) * 255.).round() as u8;
+        }
+    });
+
+    Some(Glyph {
+        left: bounds.min.x as i64,
+        top: bounds.min.y as i64,
+        width,
+        height,
+        coverage,
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,118 @@
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use ab_glyph::{GlyphId, ScaleFont as _, point};
+
+use super::{GlyphCache, SUBPIXEL_STEPS, split};
+use crate::font::Fonts;
+
+fn fonts() -> Fonts {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fonts/DejaVuSansMono.ttf");
+    Fonts::from_data(vec![fs::read(path).unwrap()], 16)
+}
+
+fn glyph_id(fonts: &Fonts, c: char) -> GlyphId {
+    fonts.get(0).glyph_id(c)
+}
+
+#[test]
+fn subpixel_steps() {
+    assert_eq!(split(3.), (3, 0));
+    assert_eq!(split(3.3), (3, 1));
+    assert_eq!(split(3.5), (3, 2));
+    assert_eq!(split(3.9), (4, 0));
+    assert_eq!(split(-0.25), (-1, 3));
+}
+
+#[test]
+fn repeated_glyphs_are_reused() {
+    let fonts = fonts();
+    let mut cache = GlyphCache::default();
+    let id = glyph_id(&fonts, '1');
+
+    let (x, y, first) = cache.get(&fonts, 0, id, 10.25, 0.).unwrap();
+    assert_eq!((x, y), (10, 0));
+
+    // another whole pixel with the same offset is the same glyph
+    let (x, _, second) = cache.get(&fonts, 0, id, 42.25, 0.).unwrap();
+    assert_eq!(x, 42);
+    assert!(Rc::ptr_eq(&first, &second));
+
+    // but another offset is rasterized on its own
+    let (_, _, third) = cache.get(&fonts, 0, id, 42.5, 0.).unwrap();
+    assert!(!Rc::ptr_eq(&first, &third));
+    assert_eq!(cache.entries.len(), 2);
+}
+
+#[test]
+fn cached_glyphs_match_direct_rasterization() {
+    let fonts = fonts();
+    let font = fonts.get(0);
+    let mut cache = GlyphCache::default();
+
+    for c in ['a', 'g', 'W', '@', '/'] {
+        let id = glyph_id(&fonts, c);
+
+        for (x, y) in [(0., 0.), (10.25, 3.), (42.6, 0.5), (-3.4, 11.75)] {
+            let (whole_x, whole_y, glyph) = cache.get(&fonts, 0, id, x, y).unwrap();
+
+            // the cached glyph is placed at x rounded to the subpixel steps, and at the exact y
+            let quantize = |position: f32| (position * SUBPIXEL_STEPS).round() / SUBPIXEL_STEPS;
+            let position = point(quantize(x), y);
+            let outline = font
+                .outline_glyph(id.with_scale_and_position(font.scale(), position))
+                .unwrap();
+            let bounds = outline.px_bounds();
+
+            assert_eq!(whole_x + glyph.left, bounds.min.x as i64, "{c} at {x}");
+            assert_eq!(whole_y + glyph.top, bounds.min.y as i64, "{c} at {y}");
+            assert_eq!(glyph.width, bounds.width() as u32, "{c}");
+            assert_eq!(glyph.height, bounds.height() as u32, "{c}");
+
+            outline.draw(|px, py, coverage| {
+                let expected = (coverage.clamp(0., 1.) * 255.).round() as u8;
+                let actual = glyph.coverage[(py * glyph.width + px) as usize];
+                assert!(
+                    actual.abs_diff(expected) <= 1,
+                    "{c} at {x}x{y}, pixel {px}x{py}: {actual} != {expected}"
+                );
+            });
+        }
+    }
+}
+
+#[test]
+fn glyphs_without_outline() {
+    let fonts = fonts();
+    let mut cache = GlyphCache::default();
+
+    assert!(
+        cache
+            .get(&fonts, 0, glyph_id(&fonts, ' '), 0., 0.)
+            .is_none()
+    );
+    assert_eq!(cache.entries.len(), 1);
+}
+
+#[test]
+fn least_recently_used_are_dropped() {
+    let fonts = fonts();
+    let mut cache = GlyphCache::with_capacity(2);
+    let [a, b, c] = ['a', 'b', 'c'].map(|c| glyph_id(&fonts, c));
+
+    let (_, _, first_a) = cache.get(&fonts, 0, a, 0., 0.).unwrap();
+    cache.get(&fonts, 0, b, 0., 0.);
+
+    // using 'a' again makes 'b' the oldest one
+    cache.get(&fonts, 0, a, 0., 0.);
+    cache.get(&fonts, 0, c, 0., 0.);
+    assert_eq!(cache.entries.len(), 2);
+
+    let (_, _, second_a) = cache.get(&fonts, 0, a, 0., 0.).unwrap();
+    assert!(Rc::ptr_eq(&first_a, &second_a));
+
+    let (_, _, _) = cache.get(&fonts, 0, b, 0., 0.).unwrap();
+    assert_eq!(cache.entries.len(), 2);
+    assert_eq!(cache.order.len(), 2);
+}
@@ -0,0 +1,141 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead as _, BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+
+use crate::bench;
+use crate::clock;
+use crate::config::{Config, DumpFormat};
+use crate::font::Fonts;
+use crate::glyph_cache::GlyphCache;
+use crate::line_reader;
+use crate::parser::{self, Size};
+use crate::pixels::{Color, Pixels};
+use crate::render::{Style, layout, render};
+use crate::token::Token;
+
+// the name that %{O:NAME} should use to target the headless output, same as the name wlroots
+// gives to the outputs of its headless backend
+pub const OUTPUT_NAME: &str = "HEADLESS-1";
+
+// renders the same way as the bar, but into memory instead of a wayland surface
+pub struct Headless {
+    pixels: Pixels,
+    fonts: Fonts,
+    glyphs: GlyphCache,
+    style: Style,
+    dump_dir: PathBuf,
+    dump_format: DumpFormat,
+    frame: u32,
+}
+
+impl Headless {
+    pub fn new(config: &Config, size: Size, dump_dir: PathBuf) -> Self {
+        let fonts = Fonts::load(&config.font, config.font_size);
+
+        if let Err(err) = fs::create_dir_all(&dump_dir) {
+            eprintln!("ERROR: can't create '{}': {}", dump_dir.display(), err);
+            std::process::exit(1);
+        }
+
+        Self {
+            pixels: Pixels::new(size.w, size.h),
+            fonts,
+            glyphs: GlyphCache::default(),
+            style: Style::new(config, 1.),
+            dump_dir,
+            dump_format: config.dump_format,
+            frame: 0,
+        }
+    }
+
+    pub fn draw_tokens(&mut self, tokens: &[Token]) {
+        let sections = layout(tokens, OUTPUT_NAME, &self.fonts, &self.style);
+
+        bench!("clear", self.pixels.clear(self.style.bg));
+        bench!("render", {
+            render(
+                &mut self.pixels,
+                tokens,
+                &sections,
+                &self.fonts,
+                &mut self.glyphs,
+                &self.style,
+            )
+        });
+
+        self.frame += 1;
+        let name = format!("frame-{:06}.{}", self.frame, self.dump_format.extension());
+        let path = self.dump_dir.join(name);
+
+        let res = bench!("dump", write_frame(&self.pixels, &path, self.dump_format));
+        if let Err(err) = res {
+            eprintln!("ERROR: can't write '{}': {}", path.display(), err);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn run(config: Config, size: Size, dump_dir: PathBuf) {
+    let mut headless = Headless::new(&config, size, dump_dir);
+
+    for line in io::stdin().lock().split(b'\n') {
+        let line = match line {
+            Ok(line) => line_reader::decode(&line),
+            Err(err) => {
+                eprintln!("ERROR: can't read stdin: {}", err);
+                std::process::exit(1);
+            }
+        };
+
+        let Some(tokens) = bench!("parsing", parser::parse_line(line.trim(), config.strict)) else {
+            continue;
+        };
+
+        let clocks = clock::format_all(&tokens, Local::now());
+        let tokens = clock::expand(&tokens, &clocks);
+        bench!("draw", headless.draw_tokens(&tokens));
+    }
+}
+
+pub fn write_frame(pixels: &Pixels, path: &Path, format: DumpFormat) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    match format {
+        DumpFormat::Png => {
+            let mut encoder = png::Encoder::new(&mut writer, pixels.width(), pixels.height());
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+
+            let data: Vec<u8> = colors(pixels)
+                .flat_map(|color| [color.r, color.g, color.b, color.a])
+                .collect();
+
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&data))
+                .map_err(io::Error::other)?;
+        }
+
+        // ppm has no alpha channel
+        DumpFormat::Ppm => {
+            write!(writer, "P6\n{} {}\n255\n", pixels.width(), pixels.height())?;
+
+            let data: Vec<u8> = colors(pixels)
+                .flat_map(|color| [color.r, color.g, color.b])
+                .collect();
+
+            writer.write_all(&data)?;
+        }
+    }
+
+    writer.flush()
+}
+
+// all of the pixels, row by row
+fn colors(pixels: &Pixels) -> impl Iterator<Item = Color> {
+    (0..pixels.height())
+        .flat_map(move |y| (0..pixels.width()).map(move |x| (x, y)))
+        .filter_map(|(x, y)| pixels.get(x, y))
+}
@@ -0,0 +1,73 @@
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, RawFd};
+
+use nonblock::NonBlockingReader;
+
+// what was read from the input in a single poll
+#[derive(Debug, Default)]
+pub struct Input {
+    // the newest complete line, if any line was completed
+    pub line: Option<String>,
+    pub eof: bool,
+}
+
+// reads lines without blocking, while keeping partial lines between reads
+pub struct LineReader<R: AsRawFd + Read> {
+    reader: NonBlockingReader<R>,
+    fd: RawFd,
+    buf: Vec<u8>,
+}
+
+impl<R: AsRawFd + Read> LineReader<R> {
+    pub fn new(reader: R) -> io::Result<Self> {
+        // the non-blocking reader doesn't give access to the fd
+        let fd = reader.as_raw_fd();
+
+        Ok(Self {
+            reader: NonBlockingReader::from_fd(reader)?,
+            fd,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn read(&mut self) -> io::Result<Input> {
+        self.reader.read_available(&mut self.buf)?;
+        let eof = self.reader.is_eof();
+
+        let mut line = None;
+
+        // only the newest complete line matters, older ones are already outdated
+        if let Some(end) = self.buf.iter().rposition(|&b| b == b'\n') {
+            let start = self.buf[..end]
+                .iter()
+                .rposition(|&b| b == b'\n')
+                .map_or(0, |index| index + 1);
+
+            line = Some(decode(&self.buf[start..end]));
+            self.buf.drain(..=end);
+        }
+
+        // the last line doesn't have to end with a newline
+        if eof && !self.buf.is_empty() {
+            line = Some(decode(&self.buf));
+            self.buf.clear();
+        }
+
+        Ok(Input { line, eof })
+    }
+}
+
+impl<R: AsRawFd + Read> AsRawFd for LineReader<R> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+// invalid utf8 is replaced instead of rejecting the whole line
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests;
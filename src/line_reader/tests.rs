@@ -0,0 +1,72 @@
+use std::io::{self, Write as _};
+
+use super::LineReader;
+
+#[test]
+fn partial_lines_are_kept() {
+    let (reader, mut writer) = io::pipe().unwrap();
+    let mut reader = LineReader::new(reader).unwrap();
+
+    writer.write_all(b"first\nsec").unwrap();
+    let input = reader.read().unwrap();
+    assert_eq!(input.line.as_deref(), Some("first"));
+    assert!(!input.eof);
+
+    writer.write_all(b"ond").unwrap();
+    let input = reader.read().unwrap();
+    assert_eq!(input.line, None);
+
+    writer.write_all(b"\n").unwrap();
+    let input = reader.read().unwrap();
+    assert_eq!(input.line.as_deref(), Some("second"));
+}
+
+#[test]
+fn newest_line_is_used() {
+    let (reader, mut writer) = io::pipe().unwrap();
+    let mut reader = LineReader::new(reader).unwrap();
+
+    writer.write_all(b"old\nnew\r\nnewer but partial").unwrap();
+    let input = reader.read().unwrap();
+    assert_eq!(input.line.as_deref(), Some("new"));
+}
+
+#[test]
+fn split_utf8_is_joined() {
+    let (reader, mut writer) = io::pipe().unwrap();
+    let mut reader = LineReader::new(reader).unwrap();
+
+    let bytes = "ñ\n".as_bytes();
+    writer.write_all(&bytes[..1]).unwrap();
+    assert_eq!(reader.read().unwrap().line, None);
+
+    writer.write_all(&bytes[1..]).unwrap();
+    assert_eq!(reader.read().unwrap().line.as_deref(), Some("ñ"));
+}
+
+#[test]
+fn invalid_utf8_is_replaced() {
+    let (reader, mut writer) = io::pipe().unwrap();
+    let mut reader = LineReader::new(reader).unwrap();
+
+    writer.write_all(b"a\xffb\n").unwrap();
+    assert_eq!(reader.read().unwrap().line.as_deref(), Some("a\u{fffd}b"));
+}
+
+#[test]
+fn eof_is_not_an_empty_line() {
+    let (reader, mut writer) = io::pipe().unwrap();
+    let mut reader = LineReader::new(reader).unwrap();
+
+    writer.write_all(b"\n").unwrap();
+    let input = reader.read().unwrap();
+    assert_eq!(input.line.as_deref(), Some(""));
+    assert!(!input.eof);
+
+    // the last line doesn't need a newline
+    writer.write_all(b"last").unwrap();
+    drop(writer);
+    let input = reader.read().unwrap();
+    assert_eq!(input.line.as_deref(), Some("last"));
+    assert!(input.eof);
+}
@@ -1,26 +1,54 @@
-use std::io;
+use std::io::{self, BufRead as _};
 use std::os::unix::prelude::AsRawFd as _;
 
-use clap::Parser as _;
+use clap::CommandFactory as _;
 use mio::Interest;
 use mio::unix::SourceFd;
-use wayland_client::{Connection, EventQueue};
+use wayland_client::{Connection, EventQueue, QueueHandle};
 
-const STDIN_TOKEN: mio::Token = mio::Token(0);
+const INPUT_TOKEN: mio::Token = mio::Token(0);
 const WAYLAND_TOKEN: mio::Token = mio::Token(1);
+const CLOCK_TOKEN: mio::Token = mio::Token(2);
+const CONFIG_TOKEN: mio::Token = mio::Token(3);
+const SIGNALS_TOKEN: mio::Token = mio::Token(4);
+
+// the control socket's connections use a fixed number of tokens after its own one, and the blocks
+// come last, since any number of them uses the tokens from this one and up
+const CONTROL_TOKEN: mio::Token = mio::Token(100);
+const BLOCKS_TOKEN: mio::Token = mio::Token(1000);
 
 mod bar;
+mod blocks;
+mod clock;
 mod collector;
 mod config;
+mod control;
 mod draw_state;
+mod feeder;
+mod filter;
+mod font;
+mod glyph_cache;
+mod headless;
+mod line_reader;
 mod output;
+mod painter;
 mod parser;
 mod pixels;
+mod render;
+mod signals;
 mod token;
+mod watcher;
 
 use crate::bar::Bar;
+use crate::blocks::{Blocks, BlocksConfig};
+use crate::clock::Clock;
 use crate::collector::Collector;
-use crate::config::Config;
+use crate::config::{Config, OnEof};
+use crate::control::Control;
+use crate::feeder::Feeder;
+use crate::signals::Signals;
+use crate::token::Token;
+use crate::watcher::Watcher;
 
 #[macro_export]
 macro_rules! bench {
@@ -40,7 +68,33 @@ macro_rules! bench {
 }
 
 fn main() {
-    let config = Config::parse();
+    // before anything else, so the signals that hide and show the bar can't kill it while it starts
+    signals::block();
+
+    // kept around, so the config file can be merged with the same arguments when it's reloaded
+    let matches = Config::command().get_matches();
+    let config = Config::load(&matches).unwrap_or_else(|err| {
+        eprintln!("ERROR: {}", err);
+        std::process::exit(1);
+    });
+
+    // only validates the input, so nothing else is needed
+    if config.check {
+        std::process::exit(check());
+    }
+
+    let strict = config.strict;
+
+    if config.on_eof == OnEof::Respawn && config.command.is_empty() {
+        eprintln!("ERROR: --on-eof respawn needs a command to run, e.g. `bread -- my-script`");
+        std::process::exit(1);
+    }
+
+    // the headless mode doesn't need a compositor at all
+    if let (Some(size), Some(dump_dir)) = (config.headless, config.dump_dir.clone()) {
+        headless::run(config, size, dump_dir);
+        return;
+    }
 
     // implemented the dispatch using two steps:
     // 1. collect globals from registry (struct Collector)
@@ -48,22 +102,55 @@ fn main() {
     // this is a bit similar to the [builder pattern](https://rust-unofficial.github.io/patterns/patterns/creational/builder.html)
     let conn = Connection::connect_to_env().unwrap();
 
-    // create a non blocking stdin reader
-    let stdin = std::io::stdin();
-    let stdin_fd = stdin.as_raw_fd();
-    let mut reader = nonblock::NonBlockingReader::from_fd(stdin)
-        .expect("can't open stdin for non-blocking read");
+    let blocks_config = config.blocks.as_ref().map(|path| {
+        BlocksConfig::load(path).unwrap_or_else(|err| {
+            eprintln!(
+                "ERROR: can't load the blocks from {}: {}",
+                path.display(),
+                err
+            );
+            std::process::exit(1);
+        })
+    });
+
+    // when there are no blocks, reads the lines either from stdin or from the command's stdout
+    let mut feeder = blocks_config
+        .is_none()
+        .then(|| Feeder::new(&config, INPUT_TOKEN));
+
+    // the command couldn't even start, and there's nothing else to show
+    if feeder.as_ref().is_some_and(Feeder::is_finished) {
+        std::process::exit(1);
+    }
 
+    let config_path = config.file_path();
+    let name = config.name.clone();
     let (mut state, mut event_queue) = init_bar(&conn, config);
 
     // used for polling efficiently from both stdin and the wayland socket
     let mut poll = mio::Poll::new().expect("unable to create Poll instance");
 
-    // register stdin for polling
-    let mut stdin_source = SourceFd(&stdin_fd);
-    poll.registry()
-        .register(&mut stdin_source, STDIN_TOKEN, Interest::READABLE)
-        .expect("unable to register stdin");
+    // register the input for polling
+    if let Some(feeder) = &feeder {
+        feeder.register(poll.registry());
+    }
+
+    // starts running the blocks, which register their own sources
+    let mut blocks = blocks_config.map(|config| Blocks::new(config, poll.registry(), BLOCKS_TOKEN));
+
+    // redraws the clocks in the last line, which is kept for that
+    let mut clock = Clock::new(poll.registry(), CLOCK_TOKEN);
+    let mut last_line = String::new();
+
+    // reloads the config file when it changes
+    let mut watcher =
+        config_path.and_then(|path| Watcher::new(&path, poll.registry(), CONFIG_TOKEN));
+
+    // changes the bar's settings at runtime
+    let mut control = Control::new(&name, poll.registry(), CONTROL_TOKEN);
+
+    // hides and shows the bar
+    let mut signals = Signals::new(poll.registry(), SIGNALS_TOKEN);
 
     // the events collected by polling
     let mut events = mio::Events::with_capacity(16);
@@ -83,20 +170,26 @@ fn main() {
         )
         .unwrap();
 
-    let mut buf = Vec::new();
     while state.is_running() {
         // taken from https://docs.rs/wayland-client/latest/wayland_client/struct.EventQueue.html#integrating-the-event-queue-with-other-sources-of-events
         event_queue.flush().unwrap();
         event_queue.dispatch_pending(&mut state).unwrap();
 
+        // new buffers are blank until the last line is drawn on them, and the outputs that waited for
+        // the compositor draw the last line that came meanwhile
+        if state.take_needs_redraw() {
+            redraw(&mut state, &event_queue.handle(), &mut clock, &last_line);
+            event_queue.flush().unwrap();
+        }
+
         // wayland read guard
         let read_guard = event_queue.prepare_read().unwrap();
 
         // converted to Option so that it can be taken once without ownership
         let mut read_guard = Some(read_guard);
 
-        // poll both the wayland socket and stdin
-        let res = poll.poll(&mut events, None);
+        // poll both the wayland socket and the input, while waking up for a pending respawn
+        let res = poll.poll(&mut events, feeder.as_ref().and_then(Feeder::timeout));
 
         match res {
             Ok(_) => {}
@@ -104,25 +197,40 @@ fn main() {
             Err(err) => panic!("POLLING ERROR: {err}"),
         };
 
+        if let Some(feeder) = &mut feeder {
+            feeder.respawn_if_due(poll.registry());
+
+            if feeder.is_finished() {
+                state.stop_running();
+            }
+        }
+
         // go over all of the events that resulted from the poll
         for event in events.iter() {
             match event.token() {
-                STDIN_TOKEN => {
-                    buf.clear();
-                    reader.read_available(&mut buf).unwrap();
+                INPUT_TOKEN => {
+                    let Some(feeder) = &mut feeder else {
+                        continue;
+                    };
 
-                    // NOTE: the user should always be providing utf8 valid text
-                    let input = unsafe { str::from_utf8_unchecked(&buf) };
+                    let line = feeder.read(poll.registry());
 
-                    let line = input.lines().last().unwrap_or_default();
-
-                    if line.is_empty() {
-                        state.stop_running()
+                    if feeder.is_finished() {
+                        state.stop_running();
                     }
 
-                    let tokens: Vec<_> = bench!("parsing", parser::parse(line.trim()).collect());
-
-                    bench!("draw", state.draw_tokens(&tokens));
+                    // nothing to draw until a line is complete
+                    if let Some(line) = line {
+                        let qhandle = event_queue.handle();
+                        draw_line(
+                            &mut state,
+                            &qhandle,
+                            &mut clock,
+                            &mut last_line,
+                            line,
+                            strict,
+                        );
+                    }
                 }
 
                 WAYLAND_TOKEN => {
@@ -139,6 +247,70 @@ fn main() {
                     });
                 }
 
+                CLOCK_TOKEN => {
+                    clock.tick();
+
+                    // the last line was already validated when it was drawn
+                    let tokens: Vec<_> = parser::parse(last_line.trim()).collect();
+
+                    // only redraws when the displayed time changed
+                    if clock.update(&tokens) {
+                        let tokens = clock::expand(&tokens, clock.texts());
+                        bench!("draw", state.draw_tokens(&tokens, &event_queue.handle()));
+                    }
+                }
+
+                SIGNALS_TOKEN => {
+                    if let Some(hidden) = signals.read() {
+                        state.set_hidden(hidden, &event_queue.handle());
+                    }
+                }
+
+                CONFIG_TOKEN => {
+                    let Some(watcher) = &mut watcher else {
+                        continue;
+                    };
+
+                    if !watcher.changed() {
+                        continue;
+                    }
+
+                    // a broken config is ignored, so a typo doesn't close the bar
+                    match Config::load(&matches) {
+                        Ok(config) => state.reload(config, &event_queue.handle()),
+                        Err(err) => eprintln!("WARN: not reloading the config: {}", err),
+                    }
+                }
+
+                token if control.as_ref().is_some_and(|control| control.owns(token)) => {
+                    let Some(control) = &mut control else {
+                        continue;
+                    };
+
+                    for command in control.handle(token, poll.registry()) {
+                        state.run_command(command, &event_queue.handle());
+                    }
+                }
+
+                token if blocks.as_ref().is_some_and(|blocks| blocks.owns(token)) => {
+                    let Some(blocks) = &mut blocks else {
+                        continue;
+                    };
+
+                    // only draws when one of the blocks changed its text
+                    if let Some(line) = blocks.handle(token, poll.registry()) {
+                        let qhandle = event_queue.handle();
+                        draw_line(
+                            &mut state,
+                            &qhandle,
+                            &mut clock,
+                            &mut last_line,
+                            line,
+                            strict,
+                        );
+                    }
+                }
+
                 token => {
                     eprintln!("WARN: unexpected token from polling: {:?}", token)
                 }
@@ -147,6 +319,83 @@ fn main() {
     }
 }
 
+fn draw_line(
+    bar: &mut Bar,
+    qhandle: &QueueHandle<Bar>,
+    clock: &mut Clock,
+    last_line: &mut String,
+    line: String,
+    strict: bool,
+) {
+    let Some(tokens) = bench!("parsing", parser::parse_line(line.trim(), strict)) else {
+        return;
+    };
+
+    // only a new line changes the visibility, so redrawing the same line doesn't undo a `show`
+    // that came from somewhere else
+    let hidden = tokens.iter().rev().find_map(|token| match token {
+        Token::Hidden(hidden) => Some(*hidden),
+        _ => None,
+    });
+
+    if let Some(hidden) = hidden {
+        bar.set_hidden(hidden, qhandle);
+    }
+
+    clock.update(&tokens);
+    let tokens = clock::expand(&tokens, clock.texts());
+    bench!("draw", bar.draw_tokens(&tokens, qhandle));
+
+    *last_line = line;
+}
+
+// draws the last line on the outputs that didn't draw it yet, with the clocks up to date
+fn redraw(bar: &mut Bar, qhandle: &QueueHandle<Bar>, clock: &mut Clock, last_line: &str) {
+    // the last line was already validated when it was drawn
+    let tokens: Vec<_> = parser::parse(last_line.trim()).collect();
+
+    // a clock that changed meanwhile has to be drawn on all of the outputs
+    let changed = clock.update(&tokens);
+    let tokens = clock::expand(&tokens, clock.texts());
+    if changed {
+        bench!("draw", bar.draw_tokens(&tokens, qhandle));
+    } else {
+        bench!("draw", bar.draw_dirty(&tokens, qhandle));
+    }
+}
+
+// validates every line from stdin, and returns the exit code
+fn check() -> i32 {
+    let mut code = 0;
+
+    for (index, bytes) in io::stdin().lock().split(b'\n').enumerate() {
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                eprintln!("ERROR: can't read stdin: {}", err);
+                return 1;
+            }
+        };
+
+        // the bar shows such lines with replacement characters, but they're still reported
+        if std::str::from_utf8(&bytes).is_err() {
+            eprintln!("line {}: invalid utf-8", index + 1);
+            code = 1;
+        }
+
+        let line = line_reader::decode(&bytes);
+        if let Err(errors) = parser::parse_strict(line.trim()) {
+            for err in errors {
+                eprintln!("line {}: {}", index + 1, err.report(line.trim()));
+            }
+
+            code = 1;
+        }
+    }
+
+    code
+}
+
 fn init_bar(conn: &Connection, config: Config) -> (Bar, EventQueue<Bar>) {
     let display = conn.display();
 
@@ -166,17 +415,19 @@ fn init_bar(conn: &Connection, config: Config) -> (Bar, EventQueue<Bar>) {
 
     // send the request, and react to events. this should collect all of the needed globals
     let mut collector = Collector::default();
-    collector_event_queue
-        .roundtrip(&mut collector)
-        .unwrap();
+    collector_event_queue.roundtrip(&mut collector).unwrap();
 
     // request the registry for the bar as well, since it needs to keep track of new outputs
     display.get_registry(&qhandle, ());
     let mut bar = collector.collect(config);
 
-    // this seems to be the right amount of dispatches needed to not miss the first input
-    // it should let the bar initialize the surfaces and buffers needed
-    while !bar.is_running() {
+    // the first roundtrip binds the outputs, and the second one receives their info and creates
+    // the surfaces. none of the outputs may match the filter, so the bar can't wait for one.
+    event_queue.roundtrip(&mut bar).unwrap();
+    event_queue.roundtrip(&mut bar).unwrap();
+
+    // let the bar initialize the surfaces and buffers needed, to not miss the first input
+    while !bar.is_configured() {
         event_queue.roundtrip(&mut bar).unwrap();
     }
 
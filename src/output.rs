@@ -1,168 +1,548 @@
-use ab_glyph::{Font, PxScaleFont};
+use std::ops::Range;
+
 use wayland_client::protocol::{
-    wl_buffer, wl_compositor, wl_output, wl_shm, wl_shm_pool, wl_surface,
+    wl_buffer, wl_callback, wl_output, wl_shm, wl_shm_pool, wl_surface,
 };
 use wayland_client::{Dispatch, Proxy as _, QueueHandle};
+use wayland_protocols::wp::fractional_scale::v1::client::wp_fractional_scale_v1;
+use wayland_protocols::wp::viewporter::client::wp_viewport;
 use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_shell_v1::Layer;
+use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1;
 use wayland_protocols_wlr::layer_shell::v1::client::zwlr_layer_surface_v1::{
     Anchor, KeyboardInteractivity,
 };
-use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
 
-use crate::bar::SectionInfo;
 use crate::bench;
-use crate::config::Config;
-use crate::draw_state::DrawState;
-use crate::pixels::{Color, Pixels};
+use crate::collector::Globals;
+use crate::config::{Align, BarLayer, Config, Position};
+use crate::draw_state::{ActionRegion, Run};
+use crate::font::Fonts;
+use crate::glyph_cache::GlyphCache;
+use crate::painter;
+use crate::pixels::Pixels;
+use crate::render::{self, SectionInfo, Style};
 use crate::token::Token;
 
+// the fractional scale protocol sends the scale in 120ths
+pub const SCALE_DENOMINATOR: u32 = 120;
+
 // each wl_output needs it's own zwlr_layer_surface and wl_surface
 // buffer can't shared between all surfaces, since some may have a different size
 pub struct Output {
     pub configured: bool,
     pub layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
     pub wl_surface: wl_surface::WlSurface,
-    pub buffer: wl_buffer::WlBuffer,
-    pub pixels: Pixels,
     pub output: wl_output::WlOutput,
-    pub fg: Color,
-    pub bg: Color,
+    pub name: String,
+    pub style: Style,
+    pub actions: Vec<ActionRegion>,
+
+    // the buffer is detached, so nothing is drawn until the output is created again
+    pub hidden: bool,
+
+    // the viewport scales the buffer down to the surface's size, and is only missing when the
+    // compositor doesn't support it, in which case only whole scales are used
+    viewport: Option<wp_viewport::WpViewport>,
+    pub fractional_scale: Option<wp_fractional_scale_v1::WpFractionalScaleV1>,
+
+    // the size of the surface from the last configure, in the surface's pixels
+    pub size: (u32, u32),
+
+    // in 120ths
+    scale: u32,
+
+    // the pixels are drawn into a buffer that the compositor isn't reading, which is then attached
+    // on the next refresh
+    buffers: Vec<Buffer>,
+    drawn: Option<usize>,
+
+    // the latest line wasn't drawn yet, either since the compositor didn't show the last frame or
+    // since all of the buffers were busy
+    pub dirty: bool,
+
+    // requested with every frame, and done when the compositor shows it. the compositor doesn't
+    // show the frames of outputs that aren't visible, so nothing is drawn on them meanwhile.
+    frame_callback: Option<wl_callback::WlCallback>,
+
+    // the runs of the latest drawn frame, where none means that they're unknown and the whole bar
+    // is drawn again. the damage is what changed since the frame before it.
+    shown: Option<Vec<Run>>,
+    damage: Vec<Range<i64>>,
+}
+
+// the buffers that are allocated for every size, and the most that an output may have
+const MIN_BUFFERS: usize = 2;
+const MAX_BUFFERS: usize = 3;
+
+// a buffer that is shared with the compositor
+struct Buffer {
+    pixels: Pixels,
+    wl_buffer: wl_buffer::WlBuffer,
+
+    // attached to the surface and not released yet, so the compositor may still read it
+    busy: bool,
+
+    // what the pixels show, so only the changed parts are painted again
+    runs: Option<Vec<Run>>,
+}
+
+impl Buffer {
+    fn new<T>(width: u32, height: u32, shm: &wl_shm::WlShm, qhandle: &QueueHandle<T>) -> Self
+    where
+        T: 'static,
+        T: Dispatch<wl_shm_pool::WlShmPool, ()>,
+        T: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let pixels = Pixels::new(width, height);
+
+        let pool = shm.create_pool(pixels.as_fd(), pixels.size() as i32, qhandle, ());
+        let wl_buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            pixels.stride() as i32,
+            wl_shm::Format::Argb8888,
+            qhandle,
+            (),
+        );
+
+        pool.destroy();
+
+        Self {
+            pixels,
+            wl_buffer,
+            busy: false,
+            runs: None,
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        self.wl_buffer.destroy();
+    }
 }
 
 impl Output {
+    // the scale is the one of the wl_output, which is replaced by the fractional one when the
+    // compositor prefers another scale
     pub fn create<T>(
         qhandle: &QueueHandle<T>,
-        compositor: &wl_compositor::WlCompositor,
-        layer_shell: &zwlr_layer_shell_v1::ZwlrLayerShellV1,
-        shm: &wl_shm::WlShm,
+        globals: &Globals,
         output: wl_output::WlOutput,
+        name: String,
         config: &Config,
+        scale: u32,
     ) -> Self
     where
         T: 'static,
         T: Dispatch<wl_surface::WlSurface, ()>,
         T: Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, ()>,
-        T: Dispatch<wl_shm::WlShm, ()>,
-        T: Dispatch<wl_shm_pool::WlShmPool, ()>,
-        T: Dispatch<wl_buffer::WlBuffer, ()>,
+        T: Dispatch<wp_viewport::WpViewport, ()>,
+        T: Dispatch<wp_fractional_scale_v1::WpFractionalScaleV1, ()>,
     {
-        let wl_surface = compositor.create_surface(qhandle, ());
+        let wl_surface = globals.compositor.create_surface(qhandle, ());
         let namespace = "bread".to_string();
-        let layer_surface = layer_shell.get_layer_surface(
+        let layer_surface = globals.layer_shell.get_layer_surface(
             &wl_surface,
             Some(&output),
-            Layer::Top,
+            layer(config.layer),
             namespace,
             qhandle,
             (),
         );
 
-        let anchor = if config.top {
-            Anchor::Left | Anchor::Right | Anchor::Top
+        let viewport = globals
+            .viewporter
+            .as_ref()
+            .map(|viewporter| viewporter.get_viewport(&wl_surface, qhandle, ()));
+
+        // a fractional scale can only be shown through a viewport
+        let fractional_scale = globals
+            .fractional_scale_manager
+            .as_ref()
+            .filter(|_| viewport.is_some())
+            .map(|manager| manager.get_fractional_scale(&wl_surface, qhandle, ()));
+
+        // without a viewport, the buffer's scale has to be set on the surface, which older
+        // compositors can't do
+        let scale = if viewport.is_some() || wl_surface.version() >= 3 {
+            scale.max(1) * SCALE_DENOMINATOR
         } else {
-            Anchor::Left | Anchor::Right | Anchor::Bottom
+            SCALE_DENOMINATOR
         };
 
-        layer_surface.set_size(0, config.height);
         layer_surface.set_keyboard_interactivity(KeyboardInteractivity::None);
-        layer_surface.set_anchor(anchor);
-        layer_surface.set_exclusive_zone(config.height as i32);
+        set_placement(&layer_surface, config);
         wl_surface.commit();
 
-        let pixels = Pixels::new(1, config.height);
-        let width = pixels.width() as i32;
-        let stride = pixels.stride() as i32;
-        let height = pixels.height() as i32;
-        let size = pixels.size() as i32;
-
-        let pool = shm.create_pool(pixels.as_fd(), size, qhandle, ());
-        let buffer = pool.create_buffer(
-            0,
-            width,
-            height,
-            stride,
-            wl_shm::Format::Argb8888,
-            qhandle,
-            (),
-        );
-
-        pool.destroy();
-
-        let fg = config.fg;
-        let bg = config.bg;
+        let style = Style::new(config, scale as f32 / SCALE_DENOMINATOR as f32);
 
         Self {
             configured: false,
             layer_surface,
             wl_surface,
-            buffer,
-            pixels,
             output,
-            fg,
-            bg,
+            name,
+            style,
+            actions: Vec::new(),
+            hidden: false,
+            viewport,
+            fractional_scale,
+            size: (0, 0),
+            scale,
+            buffers: Vec::new(),
+            drawn: None,
+            dirty: true,
+            frame_callback: None,
+            shown: None,
+            damage: Vec::new(),
+        }
+    }
+
+    // creates blank buffers for the surface's size at the current scale, and attaches one of them
+    pub fn allocate<T>(&mut self, shm: &wl_shm::WlShm, qhandle: &QueueHandle<T>)
+    where
+        T: 'static,
+        T: Dispatch<wl_shm_pool::WlShmPool, ()>,
+        T: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        let (width, height) = self.size;
+        let (buffer_width, buffer_height) = self.buffer_size();
+
+        // the buffers of the previous size are useless, even the busy ones
+        self.buffers.clear();
+        self.drawn = None;
+        self.dirty = true;
+
+        // the blank buffer is drawn on right away, instead of waiting for the previous frame
+        self.frame_callback = None;
+
+        for _ in 0..MIN_BUFFERS {
+            let mut buffer = Buffer::new(buffer_width, buffer_height, shm, qhandle);
+
+            // make sure everything is initialized to bg color instead of transparent
+            buffer.pixels.clear(self.style.bg);
+            buffer.runs = Some(Vec::new());
+            self.buffers.push(buffer);
+        }
+
+        self.shown = Some(Vec::new());
+        self.damage.clear();
+
+        match &self.viewport {
+            Some(viewport) => viewport.set_destination(width as i32, height as i32),
+            None if self.wl_surface.version() >= 3 => self
+                .wl_surface
+                .set_buffer_scale((self.scale / SCALE_DENOMINATOR) as i32),
+            None => {}
+        }
+
+        // attach the new buffer to the surface
+        self.wl_surface
+            .attach(Some(&self.buffers[0].wl_buffer), 0, 0);
+        self.buffers[0].busy = true;
+        self.wl_surface.commit();
+    }
+
+    // the size of the buffers in physical pixels, rounded like the fractional scale protocol asks
+    fn buffer_size(&self) -> (u32, u32) {
+        let (width, height) = self.size;
+        let physical = |size: u32| (size * self.scale + SCALE_DENOMINATOR / 2) / SCALE_DENOMINATOR;
+        (physical(width), physical(height))
+    }
+
+    // marks the buffer as free, and returns whether the latest line is waiting for it
+    pub fn release(&mut self, wl_buffer: &wl_buffer::WlBuffer) -> bool {
+        let Some(buffer) = self
+            .buffers
+            .iter_mut()
+            .find(|buffer| buffer.wl_buffer.id() == wl_buffer.id())
+        else {
+            return false;
+        };
+
+        buffer.busy = false;
+        self.dirty
+    }
+
+    // returns whether the latest line is waiting for the frame to be done
+    pub fn frame_done(&mut self, callback: &wl_callback::WlCallback) -> bool {
+        if self
+            .frame_callback
+            .as_ref()
+            .is_none_or(|frame_callback| frame_callback.id() != callback.id())
+        {
+            return false;
         }
+
+        self.frame_callback = None;
+        self.dirty
     }
 
-    pub fn draw(
+    // whether the latest line can be drawn now
+    pub fn is_ready(&self) -> bool {
+        self.dirty && self.configured && !self.hidden && self.frame_callback.is_none()
+    }
+
+    // a buffer that the compositor isn't reading, where another one is only allocated when all of
+    // them are busy
+    fn free_buffer<T>(&mut self, shm: &wl_shm::WlShm, qhandle: &QueueHandle<T>) -> Option<usize>
+    where
+        T: 'static,
+        T: Dispatch<wl_shm_pool::WlShmPool, ()>,
+        T: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        if let Some(index) = self.buffers.iter().position(|buffer| !buffer.busy) {
+            return Some(index);
+        }
+
+        if self.buffers.len() >= MAX_BUFFERS {
+            return None;
+        }
+
+        let (width, height) = self.buffer_size();
+        self.buffers.push(Buffer::new(width, height, shm, qhandle));
+
+        Some(self.buffers.len() - 1)
+    }
+
+    // returns whether the scale changed, which needs a new buffer
+    pub fn set_scale(&mut self, scale: u32) -> bool {
+        if scale == self.scale {
+            return false;
+        }
+
+        self.scale = scale;
+        self.style.scale = scale as f32 / SCALE_DENOMINATOR as f32;
+        true
+    }
+
+    // applies a changed config to the existing surface. the compositor sends a new configure when
+    // the size changes, which is when a new buffer is made
+    pub fn apply(&mut self, config: &Config) {
+        self.style = Style::new(config, self.style.scale);
+
+        // the runs don't have the style that they're painted with, so everything is painted again
+        self.shown = None;
+        for buffer in &mut self.buffers {
+            buffer.runs = None;
+        }
+
+        // older compositors can only set the layer when the surface is created
+        if self.layer_surface.version() >= 2 {
+            self.layer_surface.set_layer(layer(config.layer));
+        }
+
+        set_placement(&self.layer_surface, config);
+        self.wl_surface.commit();
+    }
+
+    // unmaps the surface and gives its space back to the windows
+    pub fn hide(&mut self) {
+        self.hidden = true;
+        self.layer_surface.set_exclusive_zone(0);
+        self.wl_surface.attach(None, 0, 0);
+        self.wl_surface.commit();
+    }
+
+    pub fn draw<T>(
         &mut self,
         tokens: &[Token],
         sections: &[SectionInfo; 3],
-        font: &PxScaleFont<impl Font>,
-    ) {
-        // do not draw if not configured
-        if !self.configured {
+        fonts: &Fonts,
+        glyphs: &mut GlyphCache,
+        shm: &wl_shm::WlShm,
+        qhandle: &QueueHandle<T>,
+    ) where
+        T: 'static,
+        T: Dispatch<wl_shm_pool::WlShmPool, ()>,
+        T: Dispatch<wl_buffer::WlBuffer, ()>,
+    {
+        if !self.is_ready() {
             return;
         }
 
-        bench!(format!("clear {}", self.output.id()), {
-            self.pixels.clear(self.bg);
+        let (width, height) = self.buffer_size();
+        let length = if self.style.vertical.is_some() {
+            height
+        } else {
+            width
+        };
+
+        let (runs, actions) = bench!(format!("layout {}", self.output.id()), {
+            render::runs(tokens, sections, fonts, &self.style, length)
         });
+        self.actions = actions;
 
-        bench!(format!("render {}", self.output.id()), {
-            let pixels_width = self.pixels.width() as f32;
-            for section in sections {
-                let start = (pixels_width - section.width) * section.mult;
-                let mut draw_state =
-                    DrawState::new(&mut self.pixels, font, start, self.fg, self.bg);
-
-                for &index in section.indices {
-                    let token = &tokens[index];
-                    match token {
-                        Token::Text(text) => draw_state.draw_text(text),
-                        Token::Fg(color) => draw_state.set_fg(*color),
-                        Token::Bg(color) => draw_state.set_bg(*color),
-                        Token::Ramp(size) => draw_state.draw_ramp(*size),
-                        Token::Section(..) => unreachable!("all sections are already handled"),
-                    }
-                }
-            }
+        // the same line doesn't need another frame
+        if self.shown.as_ref() == Some(&runs) {
+            self.dirty = false;
+            return;
+        }
+
+        // drawing into a buffer that the compositor reads would tear, so the line stays dirty
+        let Some(index) = self.free_buffer(shm, qhandle) else {
+            return;
+        };
+
+        let style = self.style;
+        let thickness = if style.vertical.is_some() {
+            width
+        } else {
+            height
+        };
+
+        let whole = 0..length as i64;
+        let mut changed = |old: &Option<Vec<Run>>| match old {
+            Some(old) => render::changed_spans(old, &runs, |run| {
+                painter::reach(run, fonts, glyphs, &style, thickness as i64)
+            }),
+            None => vec![whole.clone()],
+        };
+
+        // the buffer may be a few frames behind, so it's compared with what it shows itself, and a
+        // frame that wasn't attached yet is replaced, so its damage is kept as well
+        let spans = changed(&self.buffers[index].runs);
+        let damage = changed(&self.shown);
+
+        let buffer = &mut self.buffers[index];
+        bench!(format!("paint {}", self.output.id()), {
+            render::paint(
+                &mut buffer.pixels,
+                &runs,
+                fonts,
+                glyphs,
+                &self.style,
+                &spans,
+            );
         });
+        self.damage.extend(damage);
+
+        buffer.runs = Some(runs.clone());
+        self.shown = Some(runs);
+        self.drawn = Some(index);
+        self.dirty = false;
+    }
+
+    // the command of the innermost action area at the given position on the surface
+    pub fn action_at(&self, x: f32, y: f32) -> Option<&str> {
+        // the actions are measured along the bar in the buffer's pixels, and the bar goes down on
+        // vertical bars
+        let position = if self.style.vertical.is_some() { y } else { x };
+        let position = position * self.style.scale;
+
+        self.actions
+            .iter()
+            .filter(|action| action.contains(position))
+            .min_by(|a, b| a.width().total_cmp(&b.width()))
+            .map(|action| action.command.as_str())
     }
 
-    // uses the sections to know which parts to damage
-    pub fn refresh(&mut self, sections: &[SectionInfo; 3]) {
-        if !self.configured {
+    // attaches the drawn buffer, and damages the parts that changed since the last frame
+    pub fn refresh<T>(&mut self, qhandle: &QueueHandle<T>)
+    where
+        T: 'static,
+        T: Dispatch<wl_callback::WlCallback, ()>,
+    {
+        // attaching the buffer would map a hidden surface again
+        if !self.configured || self.hidden {
             return;
         }
 
-        self.wl_surface.attach(Some(&self.buffer), 0, 0);
+        // nothing new was drawn
+        let Some(index) = self.drawn.take() else {
+            return;
+        };
+
+        let buffer = &mut self.buffers[index];
+        self.wl_surface.attach(Some(&buffer.wl_buffer), 0, 0);
+        buffer.busy = true;
+
+        let (width, height) = (buffer.pixels.width() as i32, buffer.pixels.height() as i32);
+        for span in self.damage.drain(..) {
+            let (start, length) = (span.start as i32, (span.end - span.start) as i32);
+            let (x, y, width, height) = if self.style.vertical.is_some() {
+                (0, start, width, length)
+            } else {
+                (start, 0, length, height)
+            };
 
-        let height = self.pixels.height() as i32;
-        let pixels_width = self.pixels.width() as f32;
-        for section in sections {
-            let start_x = (pixels_width - section.width) * section.mult;
-            self.wl_surface
-                .damage(start_x as i32, 0, section.width as i32, height);
+            if self.wl_surface.version() >= 4 {
+                self.wl_surface.damage_buffer(x, y, width, height);
+            } else {
+                // older compositors only take the damage in the surface's pixels
+                self.wl_surface.damage(0, 0, i32::MAX, i32::MAX);
+            }
         }
 
+        self.frame_callback = Some(self.wl_surface.frame(qhandle, ()));
         self.wl_surface.commit();
     }
 }
 
+fn set_placement(layer_surface: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, config: &Config) {
+    let thickness = config.thickness();
+
+    let (anchor, width, height) = match config.position {
+        // without a width the bar is stretched between both sides, and otherwise a side is only
+        // anchored to align the bar to it. a bar that is anchored to neither side is centered.
+        Position::Top | Position::Bottom => {
+            let edge = if config.position == Position::Top {
+                Anchor::Top
+            } else {
+                Anchor::Bottom
+            };
+
+            match config.width {
+                None => (edge | Anchor::Left | Anchor::Right, 0, thickness),
+                Some(width) => match config.align {
+                    Align::Left => (edge | Anchor::Left, width, thickness),
+                    Align::Center => (edge, width, thickness),
+                    Align::Right => (edge | Anchor::Right, width, thickness),
+                },
+            }
+        }
+
+        // the width of a vertical bar is its thickness, so it always spans the whole edge
+        Position::Left | Position::Right => {
+            let edge = if config.position == Position::Left {
+                Anchor::Left
+            } else {
+                Anchor::Right
+            };
+
+            (edge | Anchor::Top | Anchor::Bottom, thickness, 0)
+        }
+    };
+
+    let margin = config.margin;
+    layer_surface.set_size(width, height);
+    layer_surface.set_anchor(anchor);
+    layer_surface.set_margin(margin.top, margin.right, margin.bottom, margin.left);
+    layer_surface.set_exclusive_zone(config.exclusive_pixels());
+}
+
+fn layer(layer: BarLayer) -> Layer {
+    match layer {
+        BarLayer::Background => Layer::Background,
+        BarLayer::Bottom => Layer::Bottom,
+        BarLayer::Top => Layer::Top,
+        BarLayer::Overlay => Layer::Overlay,
+    }
+}
+
 impl Drop for Output {
     fn drop(&mut self) {
+        if let Some(viewport) = &self.viewport {
+            viewport.destroy();
+        }
+
+        if let Some(fractional_scale) = &self.fractional_scale {
+            fractional_scale.destroy();
+        }
+
         self.layer_surface.destroy();
         self.wl_surface.destroy();
-        self.buffer.destroy();
     }
 }
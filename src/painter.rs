@@ -0,0 +1,261 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use ab_glyph::ScaleFont as _;
+
+use crate::config::VerticalText;
+use crate::draw_state::{Content, Run};
+use crate::font::Fonts;
+use crate::glyph_cache::{Glyph, GlyphCache};
+use crate::parser::Size;
+use crate::pixels::{Color, Pixels, Rect};
+use crate::render::Style;
+
+// paints runs in the bar's own coordinates, where x goes along the bar and y goes across it. vertical
+// bars are painted like horizontal ones that are rotated clockwise, except for stacked text which is
+// upright.
+pub struct Painter<'pixels, 'fonts> {
+    pixels: &'pixels mut Pixels,
+    fonts: &'fonts Fonts,
+    glyphs: &'pixels mut GlyphCache,
+    vertical: Option<VerticalText>,
+    line_height: u32,
+
+    // only the pixels in this range along the bar are painted
+    clip: Range<i64>,
+}
+
+impl<'pixels, 'fonts> Painter<'pixels, 'fonts> {
+    pub fn new(
+        pixels: &'pixels mut Pixels,
+        fonts: &'fonts Fonts,
+        glyphs: &'pixels mut GlyphCache,
+        style: &Style,
+    ) -> Self {
+        let clip = 0..style.length(pixels) as i64;
+
+        Self {
+            pixels,
+            fonts,
+            glyphs,
+            vertical: style.vertical,
+            line_height: style.scaled(style.line_height),
+            clip,
+        }
+    }
+
+    pub fn set_clip(&mut self, clip: Range<i64>) {
+        self.clip = clip;
+    }
+
+    // fills the clipped range
+    pub fn clear(&mut self, color: Color) {
+        let rect = self.rect(self.clip.clone(), 0..self.thickness());
+        self.pixels.fill_rect(&rect, color);
+    }
+
+    pub fn paint(&mut self, run: &Run) {
+        match &run.content {
+            Content::Text(text) if self.vertical == Some(VerticalText::Stack) => {
+                self.paint_stacked_text(run, text)
+            }
+            Content::Text(text) => self.paint_text(run, text),
+            Content::Ramp(size) => self.paint_ramp(run, *size),
+        }
+
+        self.paint_decorations(run);
+    }
+
+    fn paint_text(&mut self, run: &Run, text: &str) {
+        // fill background
+        self.fill_bg(run);
+
+        let clip = self.rect(self.clip.clone(), 0..self.thickness());
+        let placed = place_glyphs(self.fonts, self.glyphs, run, text, false, self.thickness());
+        for (font, x, y, glyph) in placed {
+            let font = self.fonts.get(font);
+
+            // each font is centered on its own
+            let center_y = (self.thickness() as f32 - font.height()) / 2.;
+            let center_y = center_y as i64;
+
+            let left = x + glyph.left;
+            let top = center_y + y + glyph.top + font.ascent() as i64;
+            self.blend_glyph(left, top, &glyph, &clip, run.fg);
+        }
+    }
+
+    // paints the characters upright, one below the other
+    fn paint_stacked_text(&mut self, run: &Run, text: &str) {
+        self.fill_bg(run);
+
+        // already in the buffer's coordinates, since the text isn't rotated
+        let clip = Rect {
+            x: 0..self.pixels.width() as i64,
+            y: self.clip.clone(),
+        };
+
+        let placed = place_glyphs(self.fonts, self.glyphs, run, text, true, self.thickness());
+        for (_, x, y, glyph) in placed {
+            self.pixels.blend_mask(
+                x + glyph.left,
+                y + glyph.top,
+                &glyph.coverage,
+                glyph.width,
+                &clip,
+                run.fg,
+            );
+        }
+    }
+
+    fn paint_ramp(&mut self, run: &Run, size: Size) {
+        let thickness = self.thickness();
+        let max_y = (thickness - size.h as i64).max(0);
+        let start = run.start.ceil() as i64;
+        let x = start..start + size.w as i64;
+
+        let empty = self.rect(x.clone(), 0..max_y);
+        self.pixels.fill_rect(&empty, run.bg);
+
+        let filled = self.rect(x, max_y..thickness);
+        self.pixels.fill_rect(&filled, run.fg);
+    }
+
+    fn fill_bg(&mut self, run: &Run) {
+        let start = run.start as i64;
+        let rect = self.rect(start..start + run.width.ceil() as i64, 0..self.thickness());
+        self.pixels.fill_rect(&rect, run.bg);
+    }
+
+    // paints the underline and overline along the whole run
+    fn paint_decorations(&mut self, run: &Run) {
+        let height = self.thickness();
+        let line_height = (self.line_height as i64).min(height);
+        let x = run.start.max(0.) as i64..run.end().ceil().max(0.) as i64;
+
+        if run.overline {
+            let rect = self.rect(x.clone(), 0..line_height);
+            self.pixels.fill_rect(&rect, run.line);
+        }
+
+        if run.underline {
+            let rect = self.rect(x, height - line_height..height);
+            self.pixels.fill_rect(&rect, run.line);
+        }
+    }
+
+    // blends a glyph that is placed in the bar's coordinates
+    fn blend_glyph(&mut self, left: i64, top: i64, glyph: &Glyph, clip: &Rect, color: Color) {
+        if self.vertical.is_none() {
+            self.pixels
+                .blend_mask(left, top, &glyph.coverage, glyph.width, clip, color);
+            return;
+        }
+
+        // the glyph is turned clockwise along with the bar
+        let x = self.pixels.width() as i64 - top - glyph.height as i64;
+        self.pixels
+            .blend_mask_rotated(x, left, &glyph.coverage, glyph.width, clip, color);
+    }
+
+    // the size of the bar across it, which is the height of a horizontal bar
+    fn thickness(&self) -> i64 {
+        if self.vertical.is_some() {
+            self.pixels.width() as i64
+        } else {
+            self.pixels.height() as i64
+        }
+    }
+
+    // turns an area in the bar's coordinates into the buffer's coordinates, where the parts
+    // outside of the clipped range are cut off. the underline ends up on the left side of
+    // vertical bars.
+    fn rect(&self, x: Range<i64>, y: Range<i64>) -> Rect {
+        let x = x.start.max(self.clip.start)..x.end.min(self.clip.end);
+
+        match self.vertical {
+            Some(_) => {
+                let width = self.pixels.width() as i64;
+                Rect {
+                    x: width - y.end..width - y.start,
+                    y: x,
+                }
+            }
+            None => Rect { x, y },
+        }
+    }
+}
+
+// the glyphs of the text along with their fonts and the whole pixels they're placed at. stacked text
+// is placed in the buffer's coordinates, and the rest in the bar's coordinates.
+fn place_glyphs(
+    fonts: &Fonts,
+    glyphs: &mut GlyphCache,
+    run: &Run,
+    text: &str,
+    stacked: bool,
+    thickness: i64,
+) -> Vec<(usize, i64, i64, Rc<Glyph>)> {
+    if stacked {
+        let stacked = fonts.stack(text, run.font);
+        return stacked
+            .glyphs
+            .into_iter()
+            .filter_map(|stacked_glyph| {
+                let font = fonts.get(stacked_glyph.font);
+
+                // each character is centered across the bar on its own
+                let left = (thickness as f32 - font.h_advance(stacked_glyph.id)) / 2.;
+                let baseline = run.start + stacked_glyph.y + font.ascent();
+
+                let (x, y, glyph) =
+                    glyphs.get(fonts, stacked_glyph.font, stacked_glyph.id, left, baseline)?;
+                Some((stacked_glyph.font, x, y, glyph))
+            })
+            .collect();
+    }
+
+    let shaped = fonts.shape(text, run.font);
+    shaped
+        .glyphs
+        .into_iter()
+        .filter_map(|shaped_glyph| {
+            let (x, y, glyph) = glyphs.get(
+                fonts,
+                shaped_glyph.font,
+                shaped_glyph.id,
+                run.start + shaped_glyph.x,
+                shaped_glyph.y,
+            )?;
+            Some((shaped_glyph.font, x, y, glyph))
+        })
+        .collect()
+}
+
+// the pixels along the bar that the run is drawn on, which includes the parts of its glyphs that
+// reach out of it (e.g. marks and italic letters)
+pub fn reach(
+    run: &Run,
+    fonts: &Fonts,
+    glyphs: &mut GlyphCache,
+    style: &Style,
+    thickness: i64,
+) -> Range<i64> {
+    let mut reach = run.span();
+    let Content::Text(text) = &run.content else {
+        return reach;
+    };
+
+    let stacked = style.is_stacked();
+    for (_, x, y, glyph) in place_glyphs(fonts, glyphs, run, text, stacked, thickness) {
+        let (start, length) = match stacked {
+            true => (y + glyph.top, glyph.height),
+            false => (x + glyph.left, glyph.width),
+        };
+
+        reach.start = reach.start.min(start);
+        reach.end = reach.end.max(start + length as i64);
+    }
+
+    reach
+}
@@ -1,117 +1,393 @@
-use std::iter;
+use std::ops::Range;
 use std::str::FromStr;
+use std::{fmt, iter};
 
+use serde::Deserialize;
+
+use crate::clock;
 use crate::pixels::Color;
 use crate::token::Token;
 
-pub fn parse<'a>(mut input: &'a str) -> impl Iterator<Item = Token<'a>> {
+// parses leniently: malformed attributes are shown as plain text
+pub fn parse<'a>(input: &'a str) -> impl Iterator<Item = Token<'a>> {
+    tokenize(input).map(|res| res.unwrap_or_else(|err| Token::Text(&input[err.span])))
+}
+
+// parses strictly: all of the errors are collected
+pub fn parse_strict(input: &str) -> Result<Vec<Token<'_>>, Vec<ParseError<'_>>> {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+
+    for res in tokenize(input) {
+        match res {
+            Ok(token) => tokens.push(token),
+            Err(err) => errors.push(err),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+// parses a line of input, where in strict mode the errors are reported to stderr and no tokens are
+// returned, so the line isn't drawn
+pub fn parse_line(line: &str, strict: bool) -> Option<Vec<Token<'_>>> {
+    if !strict {
+        return Some(parse(line).collect());
+    }
+
+    match parse_strict(line) {
+        Ok(tokens) => Some(tokens),
+        Err(errors) => {
+            for err in errors {
+                eprintln!("{}", err.report(line));
+            }
+
+            None
+        }
+    }
+}
+
+pub fn tokenize<'a>(input: &'a str) -> impl Iterator<Item = Result<Token<'a>, ParseError<'a>>> {
+    let mut rest = input;
     iter::from_fn(move || {
-        if input.is_empty() {
+        if rest.is_empty() {
             return None;
         }
 
-        let token;
-        (token, input) = parse_token(input);
-        Some(token)
+        let offset = input.len() - rest.len();
+        let res;
+        (res, rest) = parse_token(rest);
+
+        Some(res.map_err(|(kind, len)| ParseError {
+            span: offset..offset + len,
+            kind,
+        }))
     })
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
+pub struct ParseError<'a> {
+    // byte offsets in the parsed line
+    pub span: Range<usize>,
+    pub kind: ParseErrorKind<'a>,
+}
+
+#[derive(Debug, Clone)]
+pub enum ParseErrorKind<'a> {
+    Unclosed,
+    Empty,
+    Unknown(&'a str),
+    MissingValue(char),
+    InvalidColor(&'a str),
+    InvalidSize(&'a str),
+    InvalidFont(&'a str),
+    InvalidRawLength(&'a str),
+    InvalidClockFormat(&'a str),
+    ExpectedClose,
+}
+
+impl fmt::Display for ParseErrorKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Unclosed => write!(f, "unclosed attribute, expected '}}'"),
+            ParseErrorKind::Empty => write!(f, "empty attribute"),
+            ParseErrorKind::Unknown(name) => write!(f, "unknown attribute '{name}'"),
+            ParseErrorKind::MissingValue(c) => write!(f, "attribute '{c}' expects ':' and a value"),
+            ParseErrorKind::InvalidColor(color) => {
+                write!(f, "invalid color '{color}', expected [AA]RRGGBB")
+            }
+            ParseErrorKind::InvalidSize(size) => write!(f, "invalid size '{size}', expected WxH"),
+            ParseErrorKind::InvalidFont(font) => {
+                write!(f, "invalid font '{font}', expected a number from 1 or '-'")
+            }
+            ParseErrorKind::InvalidRawLength(len) => write!(
+                f,
+                "invalid raw length '{len}', expected %{{raw:LEN:TEXT}} where LEN is the byte length of TEXT"
+            ),
+            ParseErrorKind::InvalidClockFormat(format) => {
+                write!(f, "invalid clock format '{format}'")
+            }
+            ParseErrorKind::ExpectedClose => write!(f, "unexpected content, expected '}}'"),
+        }
+    }
+}
+
+impl ParseError<'_> {
+    // formats the error along with the line, and a caret under the offending span
+    pub fn report(&self, line: &str) -> String {
+        let column = line[..self.span.start].chars().count();
+        let width = line[self.span.clone()].chars().count().max(1);
+
+        format!(
+            "error at {}..{}: {}\n  {}\n  {}{}",
+            self.span.start,
+            self.span.end,
+            self.kind,
+            line,
+            " ".repeat(column),
+            "^".repeat(width),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Size {
     pub w: u32,
     pub h: u32,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Section {
     Left,
     Center,
     Right,
 }
 
-fn parse_token<'a>(input: &'a str) -> (Token<'a>, &'a str) {
-    if let Some((token, input)) = parse_non_text(input) {
-        return (token, input);
-    }
+// on error, also returns the length of the malformed attribute
+type TokenResult<'a> = Result<Token<'a>, (ParseErrorKind<'a>, usize)>;
 
-    if let Some(mut index) = input.find('%') {
-        if index == 0 {
-            index = 1 + input[1..]
-                .find('%')
-                .unwrap_or(input.len().saturating_sub(1));
-        }
+fn parse_token<'a>(input: &'a str) -> (TokenResult<'a>, &'a str) {
+    if let Some(attribute) = input.strip_prefix("%{") {
+        // the whole attribute is consumed, even when it's malformed. the text of a malformed raw
+        // attribute can't be told apart from what comes after it, so the rest of the line is
+        // skipped instead of parsing that text as attributes.
+        let len = match attribute.starts_with("raw:") {
+            true => input.len(),
+            false => input.find('}').map_or(input.len(), |index| index + 1),
+        };
 
-        let (text, input) = input.split_at(index);
-        let token = Token::Text(text);
-        (token, input)
-    } else {
-        let token = Token::Text(input);
-        (token, "")
+        return match parse_non_text(attribute) {
+            Ok((token, input)) => (Ok(token), input),
+            Err(kind) => (Err((kind, len)), &input[len..]),
+        };
     }
+
+    // "%%" is an escaped '%'
+    if let Some(input) = input.strip_prefix("%%") {
+        return (Ok(Token::Text("%")), input);
+    }
+
+    // text goes until the next attribute or escape, any other '%' is just text
+    let index = input
+        .match_indices('%')
+        .map(|(index, _)| index)
+        .find(|&index| matches!(input.as_bytes().get(index + 1), Some(b'{' | b'%')))
+        .unwrap_or(input.len());
+
+    let (text, input) = input.split_at(index);
+    (Ok(Token::Text(text)), input)
 }
 
-fn parse_non_text<'a>(mut input: &'a str) -> Option<(Token<'a>, &'a str)> {
-    input = input.strip_prefix("%{")?;
+// parses the attribute that comes after "%{"
+fn parse_non_text<'a>(mut input: &'a str) -> Result<(Token<'a>, &'a str), ParseErrorKind<'a>> {
+    // the raw text may contain '}', so it has to be handled before looking for the end
+    if let Some(input) = input.strip_prefix("raw:") {
+        return parse_raw(input);
+    }
+
+    // checked before the single letter attributes, since it starts with 'c'
+    if let Some(input) = input.strip_prefix("clock:") {
+        return parse_clock(input);
+    }
+
+    let Some(end) = input.find('}') else {
+        return Err(ParseErrorKind::Unclosed);
+    };
+
+    // the content of the whole attribute
+    let content = &input[..end];
+
+    match content {
+        "hide" => return Ok((Token::Hidden(true), &input[end + 1..])),
+        "show" => return Ok((Token::Hidden(false), &input[end + 1..])),
+        _ => {}
+    }
+
     let mut chars = input.chars();
-    let c = chars.next()?;
+    let c = chars.next().ok_or(ParseErrorKind::Unclosed)?;
     input = chars.as_str();
 
     let token = match c {
+        '}' => return Err(ParseErrorKind::Empty),
+
         'l' => Token::Section(Section::Left),
         'c' => Token::Section(Section::Center),
         'r' => Token::Section(Section::Right),
 
         'F' => {
-            input = input.strip_prefix(":")?;
+            input = strip_value_prefix(input, c)?;
             let color;
             (color, input) = parse_color(input)?;
             Token::Fg(color)
         }
 
         'B' => {
-            input = input.strip_prefix(":")?;
+            input = strip_value_prefix(input, c)?;
             let color;
             (color, input) = parse_color(input)?;
             Token::Bg(color)
         }
 
+        'U' => {
+            input = strip_value_prefix(input, c)?;
+            let color;
+            (color, input) = parse_color(input)?;
+            Token::Line(color)
+        }
+
+        '+' | '-' => {
+            let enabled = c == '+';
+            let mut chars = input.chars();
+            let token = match chars.next() {
+                Some('u') => Token::Underline(enabled),
+                Some('o') => Token::Overline(enabled),
+                _ => return Err(ParseErrorKind::Unknown(content)),
+            };
+
+            input = chars.as_str();
+            token
+        }
+
+        'T' => {
+            input = strip_value_prefix(input, c)?;
+            let font;
+            (font, input) = parse_font_index(input)?;
+            Token::Font(font)
+        }
+
         'R' => {
-            input = input.strip_prefix(":")?;
+            input = strip_value_prefix(input, c)?;
             let size;
             (size, input) = parse_size(input)?;
             Token::Ramp(size)
         }
 
-        _ => return None,
+        'O' => {
+            let name;
+            (name, input) = parse_optional_value(input)?;
+            Token::Output(name)
+        }
+
+        'A' => {
+            let command;
+            (command, input) = parse_optional_value(input)?;
+            Token::Action(command)
+        }
+
+        _ => return Err(ParseErrorKind::Unknown(content)),
     };
 
-    input = input.strip_prefix('}')?;
+    input = input
+        .strip_prefix('}')
+        .ok_or(ParseErrorKind::ExpectedClose)?;
 
-    Some((token, input))
+    Ok((token, input))
 }
 
-fn parse_size(mut input: &str) -> Option<(Size, &str)> {
-    let len = input.find('}')?;
-    let content;
-    (content, input) = input.split_at(len);
+// parses `LEN:TEXT}`, where LEN is the length of TEXT in bytes
+fn parse_raw(input: &str) -> Result<(Token<'_>, &str), ParseErrorKind<'_>> {
+    let (len_text, input) = input
+        .split_once(':')
+        .ok_or(ParseErrorKind::InvalidRawLength(""))?;
 
-    let (w, h) = content.split_once('x')?;
+    let text = len_text
+        .parse::<usize>()
+        .ok()
+        .and_then(|len| input.get(..len))
+        .ok_or(ParseErrorKind::InvalidRawLength(len_text))?;
 
-    let w = w.parse().ok()?;
-    let h = h.parse().ok()?;
-    let size = Size { w, h };
+    let input = input[text.len()..]
+        .strip_prefix('}')
+        .ok_or(ParseErrorKind::ExpectedClose)?;
 
-    Some((size, input))
+    Ok((Token::Text(text), input))
 }
 
-fn parse_color(mut input: &str) -> Option<(Color, &str)> {
-    let index = input.find('}')?;
+fn parse_clock(input: &str) -> Result<(Token<'_>, &str), ParseErrorKind<'_>> {
+    let (format, input) = split_value(input)?;
+    if !clock::is_valid_format(format) {
+        return Err(ParseErrorKind::InvalidClockFormat(format));
+    }
 
-    let color_text;
-    (color_text, input) = input.split_at(index);
+    let input = input
+        .strip_prefix('}')
+        .ok_or(ParseErrorKind::ExpectedClose)?;
 
-    let color = color_text.parse().ok()?;
-    Some((color, input))
+    Ok((Token::Clock(format), input))
+}
+
+fn strip_value_prefix(input: &str, c: char) -> Result<&str, ParseErrorKind<'_>> {
+    input
+        .strip_prefix(':')
+        .ok_or(ParseErrorKind::MissingValue(c))
+}
+
+// the value of an attribute, until the closing '}'
+fn split_value(input: &str) -> Result<(&str, &str), ParseErrorKind<'_>> {
+    let len = input.find('}').ok_or(ParseErrorKind::Unclosed)?;
+    Ok(input.split_at(len))
+}
+
+// parses either `:VALUE` or nothing at all
+fn parse_optional_value(input: &str) -> Result<(Option<&str>, &str), ParseErrorKind<'_>> {
+    let Some(input) = input.strip_prefix(':') else {
+        return Ok((None, input));
+    };
+
+    let (value, input) = split_value(input)?;
+    Ok((Some(value), input))
+}
+
+fn parse_font_index(input: &str) -> Result<(Option<usize>, &str), ParseErrorKind<'_>> {
+    let (content, input) = split_value(input)?;
+
+    if content == "-" {
+        return Ok((None, input));
+    }
+
+    // the fonts are numbered from 1, in the order they were given
+    let index = content
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| index.checked_sub(1))
+        .ok_or(ParseErrorKind::InvalidFont(content))?;
+
+    Ok((Some(index), input))
+}
+
+fn parse_size(input: &str) -> Result<(Size, &str), ParseErrorKind<'_>> {
+    let (content, input) = split_value(input)?;
+    let size = content
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidSize(content))?;
+
+    Ok((size, input))
+}
+
+fn parse_color(input: &str) -> Result<(Color, &str), ParseErrorKind<'_>> {
+    let (color_text, input) = split_value(input)?;
+    let color = color_text
+        .parse()
+        .map_err(|_| ParseErrorKind::InvalidColor(color_text))?;
+
+    Ok((color, input))
+}
+
+impl FromStr for Size {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (w, h) = s.split_once('x').ok_or(())?;
+
+        let w = w.parse().map_err(drop)?;
+        let h = h.parse().map_err(drop)?;
+        Ok(Size { w, h })
+    }
 }
 
 impl FromStr for Color {
@@ -131,3 +407,6 @@ impl FromStr for Color {
         Ok(color)
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,139 @@
+use super::{ParseErrorKind, parse, parse_strict};
+use crate::token::Token;
+
+#[test]
+fn malformed_attributes_fall_back_to_text() {
+    let tokens: Vec<_> = parse("a%{F:zz}b%{l").collect();
+
+    assert!(matches!(
+        tokens.as_slice(),
+        [
+            Token::Text("a"),
+            Token::Text("%{F:zz}"),
+            Token::Text("b"),
+            Token::Text("%{l"),
+        ]
+    ));
+}
+
+#[test]
+fn errors_point_at_the_attribute() {
+    let line = "ok %{R:10} %{F:123} %{Q:x} %{}";
+    let errors = parse_strict(line).unwrap_err();
+
+    let spans: Vec<_> = errors.iter().map(|err| &line[err.span.clone()]).collect();
+    assert_eq!(spans, ["%{R:10}", "%{F:123}", "%{Q:x}", "%{}"]);
+
+    assert!(matches!(errors[0].kind, ParseErrorKind::InvalidSize("10")));
+    assert!(matches!(
+        errors[1].kind,
+        ParseErrorKind::InvalidColor("123")
+    ));
+    assert!(matches!(errors[2].kind, ParseErrorKind::Unknown("Q:x")));
+    assert!(matches!(errors[3].kind, ParseErrorKind::Empty));
+}
+
+#[test]
+fn report_has_a_caret_under_the_span() {
+    let line = "ñ %{B}";
+    let errors = parse_strict(line).unwrap_err();
+
+    let report = errors[0].report(line);
+    let caret = report.lines().last().unwrap();
+    assert_eq!(caret, "    ^^^^");
+}
+
+#[test]
+fn escaped_percent() {
+    let tokens: Vec<_> = parse("50%% %{ 100% %").collect();
+
+    assert!(matches!(
+        tokens.as_slice(),
+        [
+            Token::Text("50"),
+            Token::Text("%"),
+            Token::Text(" "),
+            Token::Text("%{ 100% %"),
+        ]
+    ));
+}
+
+#[test]
+fn raw_text() {
+    let tokens = parse_strict("%{raw:11:%{F:ff0000}}}%{raw:0:}%{raw:4:ñ}}}").unwrap();
+
+    assert!(matches!(
+        tokens.as_slice(),
+        [
+            Token::Text("%{F:ff0000}"),
+            Token::Text("}"),
+            Token::Text(""),
+            Token::Text("ñ}}")
+        ]
+    ));
+}
+
+#[test]
+fn invalid_raw_length() {
+    // the length doesn't fall on a char boundary
+    let errors = parse_strict("%{raw:1:ñ}").unwrap_err();
+    assert!(matches!(
+        errors[0].kind,
+        ParseErrorKind::InvalidRawLength("1")
+    ));
+
+    // the length is too short
+    let errors = parse_strict("%{raw:1:ab}").unwrap_err();
+    assert!(matches!(errors[0].kind, ParseErrorKind::ExpectedClose));
+}
+
+#[test]
+fn invalid_raw_text_stays_text() {
+    // the length is one byte short, and doesn't fall on a char boundary
+    for line in ["%{raw:3:a}%{F:ff0000}}x", "%{raw:1:ñ}%{B:ff0000}x"] {
+        let tokens: Vec<_> = parse(line).collect();
+        assert!(
+            matches!(tokens.as_slice(), [Token::Text(text)] if *text == line),
+            "{line}: {tokens:?}"
+        );
+    }
+
+    // the text before it is still parsed
+    let tokens: Vec<_> = parse("%{B:ff0000}%{raw:2:ñb}%{F:ff0000}}").collect();
+    assert!(matches!(
+        tokens.as_slice(),
+        [Token::Bg(_), Token::Text("%{raw:2:ñb}%{F:ff0000}}")]
+    ));
+
+    let errors = parse_strict("%{raw:3:a}%{F:ff0000}}x").unwrap_err();
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn clock() {
+    let tokens: Vec<_> = parse("%{clock:%H:%M}%{c}").collect();
+    assert!(matches!(
+        tokens.as_slice(),
+        [Token::Clock("%H:%M"), Token::Section(_)]
+    ));
+
+    let errors = parse_strict("%{clock:%Q}").unwrap_err();
+    assert!(matches!(
+        errors[0].kind,
+        ParseErrorKind::InvalidClockFormat("%Q")
+    ));
+}
+
+#[test]
+fn visibility() {
+    let tokens: Vec<_> = parse("%{hide}a%{show}%{shows}").collect();
+    assert!(matches!(
+        tokens.as_slice(),
+        [
+            Token::Hidden(true),
+            Token::Text("a"),
+            Token::Hidden(false),
+            Token::Text("%{shows}"),
+        ]
+    ));
+}
@@ -1,8 +1,16 @@
+use std::ops::Range;
 use std::os::unix::prelude::{AsFd as _, BorrowedFd};
 
 use memfd::{Memfd, MemfdOptions};
 use memmap2::{Advice, MmapMut, MmapOptions};
 
+// an area of the pixels, which may reach out of them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub x: Range<i64>,
+    pub y: Range<i64>,
+}
+
 // in the ARGB format
 pub struct Pixels {
     mfd: Memfd,
@@ -58,25 +66,166 @@ impl Pixels {
     }
 
     pub fn clear(&mut self, color: Color) {
-        let mut bytes = color.as_argb().into_iter().cycle();
-        self.mmap.fill_with(|| bytes.next().unwrap_or_default());
+        self.words_mut().fill(color.as_word());
+    }
+
+    // fills the part of the row that is inside of the pixels
+    pub fn fill_row(&mut self, y: i64, x: Range<i64>, color: Color) {
+        let Ok(y) = u32::try_from(y) else {
+            return;
+        };
+
+        if y >= self.height() {
+            return;
+        }
+
+        let x = self.clamp_x(x);
+        self.row_mut(y)[x].fill(color.as_word());
+    }
+
+    // fills the part of the area that is inside of the pixels
+    pub fn fill_rect(&mut self, rect: &Rect, color: Color) {
+        for y in self.clamp_y(rect.y.clone()) {
+            self.fill_row(y as i64, rect.x.clone(), color);
+        }
+    }
+
+    // blends the color into the pixels by the coverage of each pixel (0 to 255), which is given
+    // row by row with its top left corner at x, y. only the pixels in the clipped area are changed.
+    pub fn blend_mask(
+        &mut self,
+        x: i64,
+        y: i64,
+        mask: &[u8],
+        mask_width: u32,
+        clip: &Rect,
+        color: Color,
+    ) {
+        if mask_width == 0 {
+            return;
+        }
+
+        let mask_height = (mask.len() / mask_width as usize) as i64;
+        let rows = self.clamp_y(clip.y.start.max(y)..clip.y.end.min(y + mask_height));
+        let columns = self.clamp_x(clip.x.start.max(x)..clip.x.end.min(x + mask_width as i64));
+        if columns.is_empty() {
+            return;
+        }
+
+        let color = color.as_word();
+        for row_y in rows {
+            let mask_start = (row_y as i64 - y) as usize * mask_width as usize
+                + (columns.start as i64 - x) as usize;
+            let mask_row = &mask[mask_start..mask_start + columns.len()];
+
+            let row = &mut self.row_mut(row_y)[columns.clone()];
+            for (pixel, &coverage) in row.iter_mut().zip(mask_row) {
+                *pixel = blend(*pixel, color, coverage);
+            }
+        }
+    }
+
+    // like blend_mask, but with the mask turned clockwise, so its rows become columns from right to
+    // left. x, y is the top left corner of the turned mask.
+    pub fn blend_mask_rotated(
+        &mut self,
+        x: i64,
+        y: i64,
+        mask: &[u8],
+        mask_width: u32,
+        clip: &Rect,
+        color: Color,
+    ) {
+        if mask_width == 0 {
+            return;
+        }
+
+        let mask_width = mask_width as usize;
+        let mask_height = mask.len() / mask_width;
+        let rows = self.clamp_y(clip.y.start.max(y)..clip.y.end.min(y + mask_width as i64));
+        let columns = self.clamp_x(clip.x.start.max(x)..clip.x.end.min(x + mask_height as i64));
+        if columns.is_empty() {
+            return;
+        }
+
+        let color = color.as_word();
+        for row_y in rows {
+            // each row is a column of the mask, read from its bottom up
+            let mask_x = (row_y as i64 - y) as usize;
+            let row = &mut self.row_mut(row_y)[columns.clone()];
+            for (pixel, column_x) in row.iter_mut().zip(columns.clone()) {
+                let mask_y = mask_height - 1 - (column_x as i64 - x) as usize;
+                *pixel = blend(*pixel, color, mask[mask_y * mask_width + mask_x]);
+            }
+        }
     }
 
-    pub fn set(&mut self, x: u32, y: u32, color: Color) -> bool {
+    pub fn get(&self, x: u32, y: u32) -> Option<Color> {
         if x >= self.width() || y >= self.height() {
-            return false;
+            return None;
         }
 
         let index = x * 4 + y * self.stride();
         let index = index as usize;
 
-        self.mmap[index..index + 4].copy_from_slice(&color.as_argb());
+        let [b, g, r, a] = self.mmap[index..index + 4].try_into().unwrap();
+        Some(Color::new(r, g, b, a))
+    }
+
+    // a word for each pixel, with the channels in the same order as in memory
+    fn words_mut(&mut self) -> &mut [u32] {
+        // the mapping starts at a page, which is always aligned for words
+        let (prefix, words, suffix) = unsafe { self.mmap.align_to_mut::<u32>() };
+        debug_assert!(prefix.is_empty() && suffix.is_empty());
+        words
+    }
+
+    fn row_mut(&mut self, y: u32) -> &mut [u32] {
+        let width = self.width() as usize;
+        let start = y as usize * width;
+        &mut self.words_mut()[start..start + width]
+    }
+
+    fn clamp_x(&self, x: Range<i64>) -> Range<usize> {
+        let width = self.width() as i64;
+        let start = x.start.clamp(0, width);
+        start as usize..x.end.clamp(start, width) as usize
+    }
 
-        true
+    fn clamp_y(&self, y: Range<i64>) -> Range<u32> {
+        let height = self.height() as i64;
+        let start = y.start.clamp(0, height);
+        start as u32..y.end.clamp(start, height) as u32
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// mixes two pixels by the coverage, where every other channel is computed together in a single
+// word. each channel gets 16 bits for its products, so nothing overflows into the next one.
+fn blend(background: u32, foreground: u32, coverage: u8) -> u32 {
+    match coverage {
+        0 => return background,
+        255 => return foreground,
+        _ => {}
+    }
+
+    const MASK: u32 = 0x00ff00ff;
+    let f = coverage as u32;
+    let b = 255 - f;
+
+    let mix = |background: u32, foreground: u32| {
+        let mixed = background * b + foreground * f + 0x00800080;
+
+        // divides each channel by 255, rounded
+        ((mixed + ((mixed >> 8) & MASK)) >> 8) & MASK
+    };
+
+    let red_blue = mix(background & MASK, foreground & MASK);
+    let alpha_green = mix((background >> 8) & MASK, (foreground >> 8) & MASK);
+
+    red_blue | (alpha_green << 8)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     pub a: u8,
     pub r: u8,
@@ -93,19 +242,11 @@ impl Color {
         [self.b, self.g, self.r, self.a]
     }
 
-    pub fn interpolate(self, other: Self, f: f32) -> Self {
-        let f = f.clamp(0., 1.);
-
-        let r = self.r as f32 * (1. - f) + other.r as f32 * f;
-        let g = self.g as f32 * (1. - f) + other.g as f32 * f;
-        let b = self.b as f32 * (1. - f) + other.b as f32 * f;
-        let a = self.a as f32 * (1. - f) + other.a as f32 * f;
-
-        let r = r.round().clamp(0., 255.) as u8;
-        let g = g.round().clamp(0., 255.) as u8;
-        let b = b.round().clamp(0., 255.) as u8;
-        let a = a.round().clamp(0., 255.) as u8;
-
-        Self { r, g, b, a }
+    // the pixel as it's stored in memory
+    pub fn as_word(&self) -> u32 {
+        u32::from_ne_bytes(self.as_argb())
     }
 }
+
+#[cfg(test)]
+mod tests;
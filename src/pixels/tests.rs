@@ -0,0 +1,145 @@
+use super::{Color, Pixels, Rect, blend};
+
+const BLACK: Color = Color {
+    a: 0xff,
+    r: 0,
+    g: 0,
+    b: 0,
+};
+
+const WHITE: Color = Color {
+    a: 0xff,
+    r: 0xff,
+    g: 0xff,
+    b: 0xff,
+};
+
+// the pixels as rows of characters, where '#' is white and '.' is black
+fn rows(pixels: &Pixels) -> Vec<String> {
+    (0..pixels.height())
+        .map(|y| {
+            (0..pixels.width())
+                .map(|x| match pixels.get(x, y).unwrap() {
+                    WHITE => '#',
+                    BLACK => '.',
+                    _ => '?',
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn clear() {
+    let mut pixels = Pixels::new(3, 2);
+    let color = Color::new(1, 2, 3, 4);
+    pixels.clear(color);
+
+    assert_eq!(pixels.get(0, 0), Some(color));
+    assert_eq!(pixels.get(2, 1), Some(color));
+}
+
+#[test]
+fn fill_row() {
+    let mut pixels = Pixels::new(4, 2);
+    pixels.clear(BLACK);
+    pixels.fill_row(0, 1..3, WHITE);
+
+    // out of bounds parts are skipped
+    pixels.fill_row(1, -2..1, WHITE);
+    pixels.fill_row(1, 3..10, WHITE);
+    pixels.fill_row(-1, 0..4, WHITE);
+    pixels.fill_row(2, 0..4, WHITE);
+
+    assert_eq!(rows(&pixels), [".##.", "#..#"]);
+}
+
+#[test]
+fn fill_rect() {
+    let mut pixels = Pixels::new(4, 3);
+    pixels.clear(BLACK);
+    pixels.fill_rect(&Rect { x: 2..8, y: -1..2 }, WHITE);
+
+    // empty areas don't fill anything, even when they're reversed after being clipped
+    #[allow(clippy::reversed_empty_ranges)]
+    pixels.fill_rect(&Rect { x: 3..1, y: 0..3 }, WHITE);
+    pixels.fill_rect(&Rect { x: 5..9, y: 0..3 }, WHITE);
+
+    assert_eq!(rows(&pixels), ["..##", "..##", "...."]);
+}
+
+#[test]
+fn blend_mask() {
+    let mut pixels = Pixels::new(4, 3);
+    pixels.clear(BLACK);
+
+    #[rustfmt::skip]
+    let mask = [
+        255, 0,
+        0, 255,
+        255, 255,
+    ];
+
+    // the last row and the first column are clipped
+    let clip = Rect { x: 2..4, y: 0..2 };
+    pixels.blend_mask(1, 0, &mask, 2, &clip, WHITE);
+    assert_eq!(rows(&pixels), ["....", "..#.", "...."]);
+
+    // the parts outside of the pixels are skipped
+    let clip = Rect { x: -4..8, y: -4..8 };
+    pixels.blend_mask(-1, 2, &mask, 2, &clip, WHITE);
+    assert_eq!(rows(&pixels), ["....", "..#.", "...."]);
+}
+
+#[test]
+fn blend_mask_rotated() {
+    let mut pixels = Pixels::new(4, 3);
+    pixels.clear(BLACK);
+
+    #[rustfmt::skip]
+    let mask = [
+        255, 0,
+        0, 255,
+        255, 255,
+    ];
+
+    // the rows of the mask become columns from right to left
+    let clip = Rect { x: 0..4, y: 0..3 };
+    pixels.blend_mask_rotated(0, 0, &mask, 2, &clip, WHITE);
+    assert_eq!(rows(&pixels), ["#.#.", "##..", "...."]);
+
+    // the last column is clipped
+    pixels.clear(BLACK);
+    let clip = Rect { x: 0..3, y: 0..3 };
+    pixels.blend_mask_rotated(1, 1, &mask, 2, &clip, WHITE);
+    assert_eq!(rows(&pixels), ["....", ".#..", ".##."]);
+}
+
+#[test]
+fn blending() {
+    let word = |color: Color| color.as_word();
+    let red = Color::new(0xff, 0, 0, 0xff);
+    let blue = Color::new(0, 0, 0xff, 0x80);
+
+    assert_eq!(blend(word(red), word(blue), 0), word(red));
+    assert_eq!(blend(word(red), word(blue), 255), word(blue));
+
+    // each channel is rounded on its own
+    let mixed = Color::new(0xff - 0x40, 0, 0x40, 0xff - 0x20);
+    assert_eq!(blend(word(red), word(blue), 0x40), word(mixed));
+
+    // matches the exact result, rounded
+    for coverage in 0..=255u32 {
+        for (background, foreground) in [(0u32, 255u32), (255, 0), (17, 200), (200, 17)] {
+            let expected = (background * (255 - coverage) + foreground * coverage + 127) / 255;
+            let color = |value: u32| Color::new(value as u8, 0, 0, 0).as_word();
+
+            let mixed = blend(color(background), color(foreground), coverage as u8);
+            assert_eq!(
+                mixed,
+                color(expected),
+                "{background} {foreground} {coverage}"
+            );
+        }
+    }
+}
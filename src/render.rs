@@ -0,0 +1,273 @@
+use std::ops::Range;
+
+use crate::config::{Config, VerticalText};
+use crate::draw_state::{ActionRegion, DrawState, Run};
+use crate::font::Fonts;
+use crate::glyph_cache::GlyphCache;
+use crate::painter::{self, Painter};
+use crate::parser::Section;
+use crate::pixels::{Color, Pixels};
+use crate::token::Token;
+
+pub struct SectionInfo {
+    pub width: f32,
+    pub mult: f32,
+    pub indices: Vec<usize>,
+}
+
+// the initial drawing state of each section
+#[derive(Debug, Clone, Copy)]
+pub struct Style {
+    pub fg: Color,
+    pub bg: Color,
+    pub line_height: u32,
+
+    // how the text is drawn, only set on vertical bars
+    pub vertical: Option<VerticalText>,
+
+    // the ratio between the buffer's pixels and the surface's pixels
+    pub scale: f32,
+
+    // in the surface's pixels like the line height, so it follows the scale when it changes
+    pub font_size: u32,
+}
+
+impl Style {
+    pub fn new(config: &Config, scale: f32) -> Self {
+        Self {
+            fg: config.fg,
+            bg: config.bg,
+            line_height: config.underline_height,
+            vertical: config
+                .position
+                .is_vertical()
+                .then_some(config.vertical_text),
+            scale,
+            font_size: config.font_size,
+        }
+    }
+
+    // converts a size that was given in the surface's pixels to the buffer's pixels
+    pub fn scaled(&self, size: u32) -> u32 {
+        (size as f32 * self.scale).round() as u32
+    }
+
+    // the size that the fonts are loaded with, in the buffer's pixels
+    pub fn scaled_font_size(&self) -> u32 {
+        self.scaled(self.font_size)
+    }
+
+    pub fn is_stacked(&self) -> bool {
+        self.vertical == Some(VerticalText::Stack)
+    }
+
+    // the size of the pixels along the bar
+    pub fn length(&self, pixels: &Pixels) -> u32 {
+        if self.vertical.is_some() {
+            pixels.height()
+        } else {
+            pixels.width()
+        }
+    }
+
+    // the size of the pixels across the bar
+    pub fn thickness(&self, pixels: &Pixels) -> u32 {
+        if self.vertical.is_some() {
+            pixels.width()
+        } else {
+            pixels.height()
+        }
+    }
+}
+
+// splits the tokens that are shown on the given output into sections. the widths of the sections
+// are measured along the bar, which goes down on vertical bars.
+pub fn layout(
+    tokens: &[Token],
+    output_name: &str,
+    fonts: &Fonts,
+    style: &Style,
+) -> [SectionInfo; 3] {
+    let mut l = Vec::new();
+    let mut c = Vec::new();
+    let mut r = Vec::new();
+    let mut ptr = &mut l;
+
+    // untagged tokens are shown on every output
+    let mut visible = true;
+
+    // collect all token indices to their correct section
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Output(name) => visible = name.is_none_or(|name| name == output_name),
+
+            // the sections are switched on every output, so the content after the scope ends up in
+            // the same section everywhere
+            Token::Section(Section::Left) => ptr = &mut l,
+            Token::Section(Section::Center) => ptr = &mut c,
+            Token::Section(Section::Right) => ptr = &mut r,
+            _ if !visible => {}
+            _ => ptr.push(index),
+        }
+    }
+
+    let width = |indices: &[usize]| -> f32 {
+        // the forced font is kept between tokens, just like when drawing
+        let mut font = None;
+        indices
+            .iter()
+            .map(|&index| {
+                let token = &tokens[index];
+                if let Token::Font(index) = token {
+                    font = *index;
+                }
+
+                token.px_width(fonts, font, style)
+            })
+            .sum()
+    };
+
+    // since each output has it's own width, the calculation of the starting pixel had to be
+    // abstracted away.
+    let l_section = SectionInfo {
+        width: width(&l),
+        mult: 0., // start = (pixels - width) * 0
+        indices: l,
+    };
+
+    let c_section = SectionInfo {
+        width: width(&c),
+        mult: 0.5, // start = (pixels - width) * 0.5
+        indices: c,
+    };
+
+    let r_section = SectionInfo {
+        width: width(&r),
+        mult: 1., // start = (pixels - width) * 1
+        indices: r,
+    };
+
+    [l_section, c_section, r_section]
+}
+
+// turns the sections into runs along the bar, which is `length` pixels long, and returns them
+// along with the clickable areas
+pub fn runs(
+    tokens: &[Token],
+    sections: &[SectionInfo; 3],
+    fonts: &Fonts,
+    style: &Style,
+    length: u32,
+) -> (Vec<Run>, Vec<ActionRegion>) {
+    let mut runs = Vec::new();
+    let mut actions = Vec::new();
+
+    for section in sections {
+        let start = (length as f32 - section.width) * section.mult;
+        let mut draw_state = DrawState::new(fonts, start, style);
+
+        for &index in &section.indices {
+            let token = &tokens[index];
+            match token {
+                Token::Text(text) => draw_state.add_text(text),
+                Token::Fg(color) => draw_state.set_fg(*color),
+                Token::Bg(color) => draw_state.set_bg(*color),
+                Token::Font(font) => draw_state.set_font(*font),
+                Token::Line(color) => draw_state.set_line(*color),
+                Token::Underline(enabled) => draw_state.set_underline(*enabled),
+                Token::Overline(enabled) => draw_state.set_overline(*enabled),
+                Token::Ramp(size) => draw_state.add_ramp(*size),
+                Token::Action(Some(command)) => draw_state.start_action(command),
+                Token::Action(None) => draw_state.end_action(),
+                // expanded into text before rendering
+                Token::Clock(..) => {}
+
+                // applied to the whole bar when the line is received
+                Token::Hidden(..) => {}
+
+                Token::Section(..) | Token::Output(..) => {
+                    unreachable!("all sections and outputs are already handled")
+                }
+            }
+        }
+
+        let (section_runs, section_actions) = draw_state.finish();
+        runs.extend(section_runs);
+        actions.extend(section_actions);
+    }
+
+    (runs, actions)
+}
+
+// clears the spans along the bar, and paints the runs that reach into them. the runs are painted in
+// order, so the pixels in the spans end up the same as when painting everything.
+pub fn paint(
+    pixels: &mut Pixels,
+    runs: &[Run],
+    fonts: &Fonts,
+    glyphs: &mut GlyphCache,
+    style: &Style,
+    spans: &[Range<i64>],
+) {
+    let thickness = style.thickness(pixels) as i64;
+    let reaches: Vec<_> = runs
+        .iter()
+        .map(|run| painter::reach(run, fonts, glyphs, style, thickness))
+        .collect();
+
+    let mut painter = Painter::new(pixels, fonts, glyphs, style);
+
+    for span in spans {
+        painter.set_clip(span.clone());
+        painter.clear(style.bg);
+
+        for (run, reach) in runs.iter().zip(&reaches) {
+            if reach.start < span.end && span.start < reach.end {
+                painter.paint(run);
+            }
+        }
+    }
+}
+
+// the spans along the bar whose pixels differ between two lists of runs, sorted and without overlaps.
+// the runs that are in both lists are painted the same, so only the other ones are covered.
+pub fn changed_spans(
+    old: &[Run],
+    new: &[Run],
+    mut reach: impl FnMut(&Run) -> Range<i64>,
+) -> Vec<Range<i64>> {
+    let removed = old.iter().filter(|run| !new.contains(run));
+    let added = new.iter().filter(|run| !old.contains(run));
+
+    let mut spans: Vec<_> = removed.chain(added).map(&mut reach).collect();
+    spans.sort_by_key(|span| span.start);
+
+    let mut merged: Vec<Range<i64>> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+
+    merged
+}
+
+// draws the sections into the pixels, and returns the clickable areas that were drawn
+pub fn render(
+    pixels: &mut Pixels,
+    tokens: &[Token],
+    sections: &[SectionInfo; 3],
+    fonts: &Fonts,
+    glyphs: &mut GlyphCache,
+    style: &Style,
+) -> Vec<ActionRegion> {
+    let length = style.length(pixels);
+    let (runs, actions) = self::runs(tokens, sections, fonts, style, length);
+    let whole = 0..length as i64;
+    paint(pixels, &runs, fonts, glyphs, style, &[whole]);
+    actions
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,362 @@
+// golden image tests: token lines are rendered the same way the bar renders them, and compared
+// against the reference images in tests/golden.
+//
+// to (re)generate the reference images after an intended change in rendering, run:
+//     BREAD_BLESS=1 cargo test
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use super::{Style, changed_spans, layout, paint, render, runs};
+use crate::config::{DumpFormat, VerticalText};
+use crate::font::Fonts;
+use crate::glyph_cache::GlyphCache;
+use crate::headless::{self, OUTPUT_NAME};
+use crate::painter::reach;
+use crate::parser;
+use crate::pixels::{Color, Pixels};
+
+const WIDTH: u32 = 240;
+const HEIGHT: u32 = 24;
+const FONT_SIZE: u32 = 16;
+
+// the maximum difference allowed per channel, to allow for tiny rasterization differences
+const CHANNEL_TOLERANCE: u8 = 2;
+
+// the maximum amount of pixels that are allowed to exceed the channel tolerance
+const PIXEL_TOLERANCE: usize = 4;
+
+fn manifest_path(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+fn fonts() -> Fonts {
+    let data = fs::read(manifest_path("tests/fonts/DejaVuSansMono.ttf")).unwrap();
+    Fonts::from_data(vec![data], FONT_SIZE)
+}
+
+fn draw(line: &str, width: u32, height: u32) -> Pixels {
+    draw_with(line, width, height, None, 1.)
+}
+
+fn style(vertical: Option<VerticalText>, scale: f32) -> Style {
+    Style {
+        fg: Color::new(0xff, 0xff, 0xff, 0xff),
+        bg: Color::new(0x00, 0x00, 0x00, 0xff),
+        line_height: 2,
+        vertical,
+        scale,
+        font_size: FONT_SIZE,
+    }
+}
+
+fn draw_with(
+    line: &str,
+    width: u32,
+    height: u32,
+    vertical: Option<VerticalText>,
+    scale: f32,
+) -> Pixels {
+    let style = style(vertical, scale);
+    let fonts = fonts().resized(style.scaled_font_size());
+    let tokens: Vec<_> = parser::parse(line).collect();
+    let sections = layout(&tokens, OUTPUT_NAME, &fonts, &style);
+
+    let mut pixels = Pixels::new(width, height);
+    pixels.clear(style.bg);
+    render(
+        &mut pixels,
+        &tokens,
+        &sections,
+        &fonts,
+        &mut GlyphCache::default(),
+        &style,
+    );
+    pixels
+}
+
+// draws the first line, and then paints only the parts that changed for the second one
+fn draw_changes(
+    first: &str,
+    second: &str,
+    width: u32,
+    height: u32,
+    vertical: Option<VerticalText>,
+) -> Pixels {
+    let style = style(vertical, 1.);
+    let fonts = fonts();
+    let length = style.length(&Pixels::new(width, height));
+
+    let runs_of = |line: &str| {
+        let tokens: Vec<_> = parser::parse(line).collect();
+        let sections = layout(&tokens, OUTPUT_NAME, &fonts, &style);
+        runs(&tokens, &sections, &fonts, &style, length).0
+    };
+
+    let (old, new) = (runs_of(first), runs_of(second));
+    let mut pixels = draw_with(first, width, height, vertical, 1.);
+    let mut glyphs = GlyphCache::default();
+    let thickness = style.thickness(&pixels) as i64;
+    let spans = changed_spans(&old, &new, |run| {
+        reach(run, &fonts, &mut glyphs, &style, thickness)
+    });
+    paint(&mut pixels, &new, &fonts, &mut glyphs, &style, &spans);
+    pixels
+}
+
+fn assert_same_pixels(actual: &Pixels, expected: &Pixels) {
+    for y in 0..expected.height() {
+        for x in 0..expected.width() {
+            assert_eq!(actual.get(x, y), expected.get(x, y), "pixel at {x}x{y}");
+        }
+    }
+}
+
+fn read_png(path: &PathBuf) -> (u32, u32, Vec<u8>) {
+    let decoder = png::Decoder::new(std::io::BufReader::new(File::open(path).unwrap()));
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!(info.color_type, png::ColorType::Rgba, "{}", path.display());
+
+    data.truncate(info.buffer_size());
+    (info.width, info.height, data)
+}
+
+fn assert_golden(name: &str, pixels: &Pixels) {
+    let path = manifest_path(&format!("tests/golden/{name}.png"));
+
+    if std::env::var_os("BREAD_BLESS").is_some() {
+        headless::write_frame(pixels, &path, DumpFormat::Png).unwrap();
+        return;
+    }
+
+    assert!(
+        path.exists(),
+        "missing reference image {}, run with BREAD_BLESS=1 to create it",
+        path.display()
+    );
+
+    let (width, height, expected) = read_png(&path);
+    assert_eq!(
+        (width, height),
+        (pixels.width(), pixels.height()),
+        "{name}: size mismatch"
+    );
+
+    let mut mismatches = 0;
+    for (index, expected) in expected.chunks_exact(4).enumerate() {
+        let x = index as u32 % width;
+        let y = index as u32 / width;
+        let color = pixels.get(x, y).unwrap();
+        let actual = [color.r, color.g, color.b, color.a];
+
+        let exceeds = actual
+            .iter()
+            .zip(expected)
+            .any(|(a, e)| a.abs_diff(*e) > CHANNEL_TOLERANCE);
+
+        if exceeds {
+            mismatches += 1;
+        }
+    }
+
+    if mismatches > PIXEL_TOLERANCE {
+        // keep the actual image around, so it can be compared by eye
+        let actual = manifest_path(&format!("target/golden/{name}.actual.png"));
+        fs::create_dir_all(actual.parent().unwrap()).unwrap();
+        headless::write_frame(pixels, &actual, DumpFormat::Png).unwrap();
+
+        panic!(
+            "{name}: {mismatches} pixels differ from {}, the actual image is at {}",
+            path.display(),
+            actual.display()
+        );
+    }
+}
+
+#[test]
+fn text_antialiasing() {
+    let pixels = draw("Hello, world! @#%&", WIDTH, HEIGHT);
+    assert_golden("text_antialiasing", &pixels);
+}
+
+#[test]
+fn ramps() {
+    let pixels = draw(
+        "%{R:6x4}%{R:6x12}%{R:6x24}%{R:6x0}%{F:ff0000}%{R:6x18}",
+        WIDTH,
+        HEIGHT,
+    );
+    assert_golden("ramps", &pixels);
+}
+
+#[test]
+fn section_alignment() {
+    let pixels = draw("%{l}left%{c}center%{r}right", WIDTH, HEIGHT);
+    assert_golden("section_alignment", &pixels);
+}
+
+#[test]
+fn color_changes() {
+    let pixels = draw(
+        "%{F:ff0000}red%{F:00ff00}green%{B:0000ff}%{F:ffffff}blue%{B:80ffff00}half",
+        WIDTH,
+        HEIGHT,
+    );
+    assert_golden("color_changes", &pixels);
+}
+
+#[test]
+fn decorations() {
+    let pixels = draw(
+        "%{+u}under%{-u} %{+o}over%{-o} %{U:ff0000}%{+u}%{+o}both%{R:8x10}",
+        WIDTH,
+        HEIGHT,
+    );
+    assert_golden("decorations", &pixels);
+}
+
+#[test]
+fn clipping() {
+    // the left section overflows the right edge, the right section overflows the left edge, and
+    // the ramp is taller than the buffer
+    let pixels = draw(
+        "%{l}%{R:4x40}left side that is way too long%{r}right side that is way too long",
+        120,
+        HEIGHT,
+    );
+    assert_golden("clipping", &pixels);
+}
+
+#[test]
+fn output_scope() {
+    let pixels = draw(
+        "all %{O:DP-1}dp-1 %{O:HEADLESS-1}headless %{O}all",
+        WIDTH,
+        HEIGHT,
+    );
+    assert_golden("output_scope", &pixels);
+}
+
+#[test]
+fn sections_in_output_scope() {
+    let style = style(None, 1.);
+    let fonts = fonts();
+    let tokens: Vec<_> = parser::parse("%{O:DP-1}%{r}dp-1%{O}right").collect();
+
+    // the section is switched on the other outputs too, only the content is left out
+    let [left, _, right] = layout(&tokens, OUTPUT_NAME, &fonts, &style);
+    assert!(left.indices.is_empty());
+    assert_eq!(right.width, fonts.text_width("right", None));
+}
+
+#[test]
+fn vertical_rotated_text() {
+    let pixels = draw_with(
+        "%{l}top%{c}%{+u}middle%{r}%{R:6x12}bottom",
+        HEIGHT,
+        WIDTH,
+        Some(VerticalText::Rotate),
+        1.,
+    );
+    assert_golden("vertical_rotated_text", &pixels);
+}
+
+#[test]
+fn vertical_stacked_text() {
+    let pixels = draw_with(
+        "%{l}top%{c}%{B:0000ff}mid%{r}%{+o}end",
+        HEIGHT,
+        WIDTH,
+        Some(VerticalText::Stack),
+        1.,
+    );
+    assert_golden("vertical_stacked_text", &pixels);
+}
+
+#[test]
+fn scaled() {
+    // the same line as in `decorations`, on a buffer that is twice as big
+    let pixels = draw_with(
+        "%{+u}under%{-u} %{+o}over%{-o} %{U:ff0000}%{+u}%{+o}both%{R:8x10}",
+        WIDTH * 2,
+        HEIGHT * 2,
+        None,
+        2.,
+    );
+    assert_golden("scaled", &pixels);
+}
+
+#[test]
+fn unchanged_line_has_no_changes() {
+    let style = style(None, 1.);
+    let fonts = fonts();
+    let tokens: Vec<_> = parser::parse("%{l}left%{c}%{F:ff0000}center%{r}right").collect();
+    let sections = layout(&tokens, OUTPUT_NAME, &fonts, &style);
+    let (runs, _) = runs(&tokens, &sections, &fonts, &style, WIDTH);
+
+    assert!(changed_spans(&runs, &runs, |_| unreachable!()).is_empty());
+}
+
+#[test]
+fn changes_only_cover_the_changed_runs() {
+    let style = style(None, 1.);
+    let fonts = fonts();
+    let runs_of = |line: &str| {
+        let tokens: Vec<_> = parser::parse(line).collect();
+        let sections = layout(&tokens, OUTPUT_NAME, &fonts, &style);
+        runs(&tokens, &sections, &fonts, &style, WIDTH).0
+    };
+
+    let old = runs_of("%{l}left%{r}12:00");
+    let new = runs_of("%{l}left%{r}12:01");
+
+    // the right section keeps its width, so only the time is painted again
+    let mut glyphs = GlyphCache::default();
+    let spans = changed_spans(&old, &new, |run| {
+        reach(run, &fonts, &mut glyphs, &style, HEIGHT as i64)
+    });
+    assert_eq!(spans.len(), 1);
+    assert!(spans[0].start > fonts.text_width("left", None) as i64);
+}
+
+#[test]
+fn painting_changes_matches_full_render() {
+    let lines = [
+        (
+            "%{l}left%{c}center%{r}12:00",
+            "%{l}left%{c}centered%{r}12:01",
+        ),
+        (
+            "%{B:ff0000}colored%{B-} %{+u}line",
+            "colored %{+u}%{+o}lines%{R:4x8}",
+        ),
+        ("short", "a much longer line than before"),
+        ("a much longer line than before", "short"),
+        // the mark reaches far out of its run, into the unchanged one before it
+        ("abc%{F:ff0000}\u{eb1}x", "abc%{F:ff0000}yx"),
+    ];
+
+    for (first, second) in lines {
+        let expected = draw(second, WIDTH, HEIGHT);
+        assert_same_pixels(&draw_changes(first, second, WIDTH, HEIGHT, None), &expected);
+    }
+
+    for vertical in [VerticalText::Rotate, VerticalText::Stack] {
+        let (first, second) = ("%{l}top%{r}12:00", "%{l}top%{r}12:01");
+        let expected = draw_with(second, HEIGHT, WIDTH, Some(vertical), 1.);
+        let actual = draw_changes(first, second, HEIGHT, WIDTH, Some(vertical));
+        assert_same_pixels(&actual, &expected);
+    }
+}
+
+#[test]
+fn font_size_follows_the_scale() {
+    let mut style = style(None, 1.);
+    assert_eq!(style.scaled_font_size(), FONT_SIZE);
+
+    // like when the compositor prefers a fractional scale after the output was created
+    style.scale = 1.5;
+    assert_eq!(style.scaled_font_size(), 24);
+    assert_eq!(style.scaled(style.line_height), 3);
+}
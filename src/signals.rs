@@ -0,0 +1,56 @@
+use std::os::fd::AsRawFd;
+
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+
+fn mask() -> SigSet {
+    SigSet::from(Signal::SIGUSR1) | Signal::SIGUSR2
+}
+
+// the signals have to be blocked to be read from a signalfd
+pub fn block() {
+    mask()
+        .thread_block()
+        .expect("can't block SIGUSR1 and SIGUSR2");
+}
+
+// SIGUSR1 hides the bar, and SIGUSR2 shows it
+pub struct Signals {
+    signalfd: SignalFd,
+}
+
+impl Signals {
+    // the signals have to be blocked already
+    pub fn new(registry: &Registry, token: Token) -> Self {
+        let signalfd =
+            SignalFd::with_flags(&mask(), SfdFlags::SFD_NONBLOCK | SfdFlags::SFD_CLOEXEC)
+                .expect("can't create a signalfd");
+
+        registry
+            .register(
+                &mut SourceFd(&signalfd.as_raw_fd()),
+                token,
+                Interest::READABLE,
+            )
+            .expect("unable to register the signals");
+
+        Self { signalfd }
+    }
+
+    // returns whether the bar should be hidden, according to the last signal received
+    pub fn read(&mut self) -> Option<bool> {
+        let mut hidden = None;
+
+        while let Ok(Some(info)) = self.signalfd.read_signal() {
+            match Signal::try_from(info.ssi_signo as i32) {
+                Ok(Signal::SIGUSR1) => hidden = Some(true),
+                Ok(Signal::SIGUSR2) => hidden = Some(false),
+                _ => {}
+            }
+        }
+
+        hidden
+    }
+}
@@ -1,7 +1,7 @@
-use ab_glyph::{Font, PxScaleFont, ScaleFont as _};
-
+use crate::font::Fonts;
 use crate::parser::{Section, Size};
 use crate::pixels::Color;
+use crate::render::Style;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Token<'a> {
@@ -20,16 +20,50 @@ pub enum Token<'a> {
     /// %{B:[AA]RRGGBB}
     Bg(Color),
 
+    /// change of underline/overline color
+    /// %{U:[AA]RRGGBB}
+    Line(Color),
+
+    /// enable/disable underline
+    /// %{+u} %{-u}
+    Underline(bool),
+
+    /// enable/disable overline
+    /// %{+o} %{-o}
+    Overline(bool),
+
+    /// force the n-th font (starting from 1), or go back to the fallback chain
+    /// %{T:N} %{T:-}
+    Font(Option<usize>),
+
     /// ramp
     /// %{R:WxH}
     Ramp(Size),
+
+    /// limit the following tokens to a single output, or show them on all outputs
+    /// %{O:NAME} %{O}
+    Output(Option<&'a str>),
+
+    /// start of a clickable area with the given command, or the end of the last one
+    /// %{A:COMMAND} %{A}
+    Action(Option<&'a str>),
+
+    /// the current time, with a strftime-like format
+    /// %{clock:FORMAT}
+    Clock(&'a str),
+
+    /// hide the whole bar, or show it again
+    /// %{hide} %{show}
+    Hidden(bool),
 }
 
 impl Token<'_> {
-    pub fn px_width<F: Font>(&self, font: &PxScaleFont<F>) -> f32 {
+    // the length along the bar, where stacked text is as long as its characters are high
+    pub fn px_width(&self, fonts: &Fonts, font: Option<usize>, style: &Style) -> f32 {
         match self {
-            Token::Text(text) => text.chars().map(|c| font.h_advance(font.glyph_id(c))).sum(),
-            Token::Ramp(size) => size.w as f32,
+            Token::Text(text) if style.is_stacked() => fonts.stack(text, font).width,
+            Token::Text(text) => fonts.text_width(text, font),
+            Token::Ramp(size) => style.scaled(size.w) as f32,
             _ => 0.,
         }
     }
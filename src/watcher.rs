@@ -0,0 +1,63 @@
+use std::ffi::OsString;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+
+use inotify::{Inotify, WatchMask};
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
+
+// notices when the config file is changed, by watching its directory, since editors usually
+// replace the file instead of writing into it
+pub struct Watcher {
+    inotify: Inotify,
+    file_name: OsString,
+}
+
+impl Watcher {
+    // returns None when the directory doesn't exist, in which case there's nothing to watch
+    pub fn new(path: &Path, registry: &Registry, token: Token) -> Option<Self> {
+        let file_name = path.file_name()?.to_owned();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let inotify = Inotify::init().expect("can't initialize inotify");
+        let mask = WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE;
+        if let Err(err) = inotify.watches().add(dir, mask) {
+            if err.kind() != io::ErrorKind::NotFound {
+                eprintln!("WARN: can't watch {}: {}", dir.display(), err);
+            }
+
+            return None;
+        }
+
+        registry
+            .register(
+                &mut SourceFd(&inotify.as_raw_fd()),
+                token,
+                Interest::READABLE,
+            )
+            .expect("unable to register the config watcher");
+
+        Some(Self { inotify, file_name })
+    }
+
+    // reads all of the pending events, and returns whether any of them was about the config file
+    pub fn changed(&mut self) -> bool {
+        let mut buffer = [0; 4096];
+        let mut changed = false;
+
+        while let Ok(events) = self.inotify.read_events(&mut buffer) {
+            changed |= events
+                .into_iter()
+                .any(|event| event.name == Some(self.file_name.as_os_str()));
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,30 @@
+use std::fs;
+use std::time::Duration;
+
+use super::Watcher;
+
+#[test]
+fn notices_replaced_file() {
+    let dir = std::env::temp_dir().join(format!("bread-watcher-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+
+    let mut poll = mio::Poll::new().unwrap();
+    let mut events = mio::Events::with_capacity(4);
+    let mut watcher = Watcher::new(&path, poll.registry(), mio::Token(0)).unwrap();
+
+    // other files in the same directory are ignored
+    fs::write(dir.join("other.toml"), "height = 30").unwrap();
+    poll.poll(&mut events, Some(Duration::from_secs(5)))
+        .unwrap();
+    assert!(!watcher.changed());
+
+    // editors usually write a new file and move it over the old one
+    fs::write(dir.join("config.toml.tmp"), "height = 30").unwrap();
+    fs::rename(dir.join("config.toml.tmp"), &path).unwrap();
+    poll.poll(&mut events, Some(Duration::from_secs(5)))
+        .unwrap();
+    assert!(watcher.changed());
+
+    fs::remove_dir_all(&dir).unwrap();
+}